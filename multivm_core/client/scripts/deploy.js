@@ -1,6 +1,7 @@
 async function main() {
   const fs = require("fs");
   const borsh = require("borsh");
+  const { deploy_contract } = require("./utils");
 
   const [user1, user2] = await ethers.getSigners();
 
@@ -25,11 +26,7 @@ async function main() {
     "../../example_contracts/target/riscv-guest/riscv32im-risc0-zkvm-elf/release/amm"
   );
 
-  deploy_multivm_contract_from_node(
-    "user1.multivm",
-    privateKey1,
-    toHexString(bytecode)
-  );
+  await deploy_contract("user1.multivm", privateKey1, toHexString(bytecode));
 
   console.log("Calling amm...");
 
@@ -128,17 +125,6 @@ function get_balance(address) {
   return call("eth_getBalance", [address]);
 }
 
-// temporary solution until we have multivm structs in nodejs
-function deploy_multivm_contract_from_node(mvm, private_key, bytecode) {
-  return call("mvm_deployContract", [
-    {
-      multivm: mvm,
-      private_key: private_key,
-      bytecode: bytecode,
-    },
-  ]);
-}
-
 function mvm_view(account, method, args) {
   const schema = {
     struct: {
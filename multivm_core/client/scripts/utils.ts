@@ -1,4 +1,6 @@
 import { network } from "hardhat";
+import { SigningKey, concat, getBytes, hexlify, sha256, toBeHex, toUtf8Bytes } from "ethers";
+import { deserialize, serialize } from "borsh";
 let ID = 0;
 const getID = () => ++ID;
 export const defaultGas = BigInt(300_000);
@@ -53,14 +55,88 @@ export async function get_balance(address: string) {
   ]).then((r) => BigInt(r.result).toString());
 }
 
+// Builds contract deployment transaction and signs it locally, the key never leaves the client
 export async function deploy_contract(mvm: string, privateKey: string, bytecode: string) {
-  return await call("mvm_deployContract", [
-    {
-      multivm: mvm,
-      private_key: privateKey,
-      bytecode: bytecode,
-    },
+  const signingKey = new SigningKey(privateKey.startsWith("0x") ? privateKey : "0x" + privateKey);
+  // Image ID is signed as a part of the transaction, node computes it from the ELF
+  const imageId: number[] = (await call("mvm_imageId", [bytecode])).result;
+  const latestBlock = (await call("eth_getBlockByNumber", ["latest", false])).result;
+  // Nonce has to match the signer account nonce
+  const account = (await call("mvm_accountInfo", [mvm])).result;
+
+  const transaction = {
+    receiver_id: { MultiVm: "multivm" },
+    calls: [
+      {
+        method: "deploy_contract",
+        args: serialize(imageIdSchema, imageId),
+        gas: BigInt(100_000_000),
+        deposit: defaultDeposit,
+      },
+    ],
+    signer_id: { MultiVm: mvm },
+    origin_block_height: BigInt(latestBlock.number),
+    origin_block_hash: getBytes(latestBlock.hash),
+    deadline: BigInt(10),
+    nonce: BigInt(account.nonce),
+  };
+  const attachments = {
+    contracts_images: new Map([[imageId, getBytes("0x" + bytecode.replace("0x", ""))]]),
+  };
+
+  return await send_transaction(transaction, signingKey, attachments);
+}
+
+export async function send_transaction(transaction: any, signingKey: SigningKey, attachments?: any) {
+  return await call("mvm_sendTransaction", [hexlify(await sign_transaction(transaction, signingKey, attachments))]);
+}
+
+// Domain separator and payload version, as in `Transaction::signing_payload`
+export const signingPrefix = toUtf8Bytes("\x19MultiVM Signed Transaction:\n");
+export const transactionVersion = 1;
+
+// Payload signed by the signer: prefix, big-endian chain id, version and transaction hash
+export async function signing_payload(transaction: any) {
+  const chainId = BigInt((await call("eth_chainId", [])).result);
+  const transactionHash = sha256(serialize(multivmTransactionSchema, transaction));
+
+  return concat([signingPrefix, toBeHex(chainId, 8), new Uint8Array([transactionVersion]), transactionHash]);
+}
+
+// Returns Borsh encoded `SignedTransaction`, signature is over SHA-256 of the signing payload
+export async function sign_transaction(transaction: any, signingKey: SigningKey, attachments?: any) {
+  const signature = signingKey.sign(sha256(await signing_payload(transaction)));
+
+  return serialize(signedTransactionSchema, {
+    transaction,
+    signature: getBytes(concat([signature.r, signature.s])),
+    recovery_id: signature.yParity,
+    cosignatures: [],
+    attachments: attachments ?? null,
+  });
+}
+
+// Domain separator of relayer signatures, as in `DelegatedTransaction::signing_payload`
+export const delegationPrefix = toUtf8Bytes("\x19MultiVM Delegated Transaction:\n");
+
+// Wraps Borsh encoded `SignedTransaction` of the user into `DelegatedTransaction` signed by the relayer,
+// the relayer pays the fees. User transaction nonce has to match the user account nonce
+export async function relay_transaction(signedTransaction: Uint8Array, relayerId: any, relayerKey: SigningKey) {
+  const { transaction }: any = deserialize(signedTransactionSchema, signedTransaction);
+  const chainId = BigInt((await call("eth_chainId", [])).result);
+  const transactionHash = sha256(serialize(multivmTransactionSchema, transaction));
+  const delegationHash = sha256(concat([transactionHash, serialize(accountIdSchema, relayerId)]));
+  const payload = concat([delegationPrefix, toBeHex(chainId, 8), new Uint8Array([transactionVersion]), delegationHash]);
+  const signature = relayerKey.sign(sha256(payload));
+
+  const delegated = concat([
+    signedTransaction,
+    serialize(accountIdSchema, relayerId),
+    serialize(signatureSchema, getBytes(concat([signature.r, signature.s]))),
+    new Uint8Array([signature.yParity]),
   ]);
+
+  return await call("mvm_sendDelegatedTransaction", [hexlify(delegated)]);
 }
 
 export const toHexString = (byteArray: Buffer) => Array.from(byteArray, (byte) => ("0" + (byte & 0xff).toString(16)).slice(-2)).join("");
@@ -76,6 +152,42 @@ export const transactionSchema = {
   },
 };
 
+export const accountIdSchema = {
+  enum: [{ struct: { MultiVm: "string" } }, { struct: { Evm: { array: { type: "u8", len: 20 } } } }],
+};
+
+export const multivmTransactionSchema = {
+  struct: {
+    receiver_id: accountIdSchema,
+    calls: { array: { type: transactionSchema } },
+    signer_id: accountIdSchema,
+    origin_block_height: "u64",
+    origin_block_hash: { array: { type: "u8", len: 32 } },
+    deadline: "u64",
+    nonce: "u64",
+  },
+};
+
+export const imageIdSchema = { array: { type: "u32", len: 8 } };
+
+export const signatureSchema = { array: { type: "u8" } };
+
+export const signedTransactionSchema = {
+  struct: {
+    transaction: multivmTransactionSchema,
+    signature: { array: { type: "u8" } },
+    recovery_id: "u8",
+    cosignatures: { array: { type: { struct: { signature: { array: { type: "u8" } }, recovery_id: "u8" } } } },
+    attachments: {
+      option: {
+        struct: {
+          contracts_images: { map: { key: imageIdSchema, value: { array: { type: "u8" } } } },
+        },
+      },
+    },
+  },
+};
+
 export const initArgs = {
   array: { type: "u8" },
 };
@@ -1,3 +1,19 @@
+use std::collections::HashMap;
+
+use risc0_build::GuestOptions;
+
 fn main() {
-    risc0_build::embed_methods();
+    // Guest features can't be enabled by cargo, they are passed to the guest build
+    let mut features = vec![];
+    if std::env::var("CARGO_FEATURE_EVM_TRACING").is_ok() {
+        features.push("tracing".to_string());
+    }
+
+    risc0_build::embed_methods_with_options(HashMap::from([(
+        "system_meta_contract",
+        GuestOptions {
+            features,
+            ..Default::default()
+        },
+    )]));
 }
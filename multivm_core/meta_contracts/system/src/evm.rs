@@ -1,20 +1,31 @@
 use crate::{
     account_management::{self, Account, Executable},
-    system_env,
+    system_env, tracer,
 };
 use eth_primitive_types::{H160, H256, U256};
+use ethers_core::abi::{ParamType, Token};
 use evm::backend::MemoryVicinity;
-use evm::executor::stack::{MemoryStackState, StackExecutor, StackSubstateMetadata};
-use evm::{backend::ApplyBackend, Config};
-use multivm_primitives::EvmAddress;
-use std::collections::BTreeMap;
+use evm::executor::stack::{
+    IsPrecompileResult, MemoryStackState, PrecompileFailure, PrecompileHandle, PrecompileOutput,
+    PrecompileSet, StackExecutor, StackSubstateMetadata,
+};
+use evm::{backend::ApplyBackend, Config, ExitRevert, ExitSucceed};
+use multivm_primitives::{
+    AccountId, EvmAddress, EvmLog, MultiVmAccountId, BASE_FEE_PER_GAS, CHAIN_ID,
+};
+use std::collections::{BTreeMap, BTreeSet};
 
-pub fn deploy_evm_contract(owner: Account, code: Vec<u8>) {
-    let config = Config::istanbul();
+/// Reserved address (0x0000000000000000000000000000000000000400) of the precompile making MultiVM cross-contract calls
+pub const MULTIVM_CALL_PRECOMPILE: u64 = 0x400;
+/// Gas attached to MultiVM calls made by the precompile, reserved from the EVM gas
+const MULTIVM_CALL_GAS: u64 = 300_000;
+/// EVM gas charged for the precompile call on top of the reserved one
+const MULTIVM_CALL_COST: u64 = 700;
 
-    let vicinity = MemoryVicinity {
+fn vicinity(gas_price: u128) -> MemoryVicinity {
+    MemoryVicinity {
         // TODO: set these values
-        gas_price: U256::zero(),
+        gas_price: gas_price.into(),
         origin: H160::default(),
         block_hashes: Vec::new(),
         block_number: Default::default(),
@@ -22,16 +33,21 @@ pub fn deploy_evm_contract(owner: Account, code: Vec<u8>) {
         block_timestamp: Default::default(),
         block_difficulty: Default::default(),
         block_gas_limit: Default::default(),
-        chain_id: U256::one(),
-        block_base_fee_per_gas: U256::zero(),
+        chain_id: CHAIN_ID.into(),
+        block_base_fee_per_gas: BASE_FEE_PER_GAS.into(),
         block_randomness: None,
-    };
+    }
+}
+
+pub fn deploy_evm_contract(owner: Account, code: Vec<u8>, gas_limit: u64, gas_price: u128) {
+    let config = Config::istanbul();
+
+    let vicinity = vicinity(gas_price);
 
     let mut backend = EvmMemoryBackend::new(&vicinity);
-    let metadata = StackSubstateMetadata::new(u64::MAX, &config);
+    let metadata = StackSubstateMetadata::new(gas_limit, &config);
     let state = MemoryStackState::new(metadata, &backend);
-    let precompiles = BTreeMap::new();
-    let mut executor = StackExecutor::new_with_precompiles(state, &config, &precompiles);
+    let mut executor = StackExecutor::new_with_precompiles(state, &config, &Precompiles);
 
     let owner_address = owner.evm_address.into();
 
@@ -39,13 +55,32 @@ pub fn deploy_evm_contract(owner: Account, code: Vec<u8>) {
         caller: owner_address,
     });
 
-    let reason = executor.transact_create(owner_address, U256::from(0), code, u64::MAX, Vec::new());
+    let (reason, tracer) = tracer::trace(|| {
+        executor.transact_create(owner_address, U256::from(0), code, gas_limit, Vec::new())
+    });
+    let gas_used = executor.used_gas();
+    if let Some(tracer) = tracer {
+        system_env::emit_trace(tracer.finish(gas_used, &reason.0, &reason.1));
+    }
 
     let s = executor.into_state();
     let (a, b) = s.deconstruct();
     backend.apply(a, b, false);
+    let unused_gas = process_logs(backend.logs());
+    charge_gas_fee(
+        owner_address,
+        gas_used.saturating_sub(unused_gas),
+        gas_price,
+    );
+    charge_account_creation(owner_address, backend.created_accounts());
 
-    system_env::commit(((contract_address.to_fixed_bytes()), reason.1));
+    // Zero address tells that the contract wasn't created
+    let contract_address = if reason.0.is_succeed() {
+        contract_address
+    } else {
+        H160::zero()
+    };
+    system_env::commit((contract_address.to_fixed_bytes(), reason.1));
 }
 
 pub fn call_contract(
@@ -53,48 +88,183 @@ pub fn call_contract(
     contract_address: EvmAddress,
     data: Vec<u8>,
     value: u128,
+    gas_limit: u64,
+    gas_price: u128,
     apply_changes: bool,
 ) {
     let config = Config::istanbul();
 
-    let vicinity = MemoryVicinity {
-        gas_price: U256::zero(),
-        origin: H160::default(),
-        block_hashes: Vec::new(),
-        block_number: Default::default(),
-        block_coinbase: Default::default(),
-        block_timestamp: Default::default(),
-        block_difficulty: Default::default(),
-        block_gas_limit: Default::default(),
-        chain_id: U256::one(),
-        block_base_fee_per_gas: U256::zero(),
-        block_randomness: None,
-    };
+    let vicinity = vicinity(gas_price);
 
     let mut backend = EvmMemoryBackend::new(&vicinity);
-    let metadata = StackSubstateMetadata::new(u64::MAX, &config);
+    let metadata = StackSubstateMetadata::new(gas_limit, &config);
     let state = MemoryStackState::new(metadata, &backend);
-    let precompiles = BTreeMap::new();
-    let mut executor = StackExecutor::new_with_precompiles(state, &config, &precompiles);
-
-    let reason = executor.transact_call(
-        caller_address.into(),
-        contract_address.into(),
-        value.into(),
-        data,
-        u64::MAX,
-        Vec::new(),
-    );
+    let mut executor = StackExecutor::new_with_precompiles(state, &config, &Precompiles);
+
+    let (reason, tracer) = tracer::trace(|| {
+        executor.transact_call(
+            caller_address.clone().into(),
+            contract_address.into(),
+            value.into(),
+            data,
+            gas_limit,
+            Vec::new(),
+        )
+    });
+    let gas_used = executor.used_gas();
+    if let Some(tracer) = tracer {
+        system_env::emit_trace(tracer.finish(gas_used, &reason.0, &reason.1));
+    }
 
     let s = executor.into_state();
     let (a, b) = s.deconstruct();
     if apply_changes {
         backend.apply(a, b, false);
+        let unused_gas = process_logs(backend.logs());
+        charge_gas_fee(
+            caller_address.clone().into(),
+            gas_used.saturating_sub(unused_gas),
+            gas_price,
+        );
+        charge_account_creation(caller_address.into(), backend.created_accounts());
     }
 
     system_env::commit(reason.1);
 }
 
+type PrecompileResult = Result<PrecompileOutput, PrecompileFailure>;
+
+/// Precompiles of the system EVM
+struct Precompiles;
+
+impl PrecompileSet for Precompiles {
+    fn execute(&self, handle: &mut impl PrecompileHandle) -> Option<PrecompileResult> {
+        (handle.code_address() == multivm_call_address()).then(|| multivm_call(handle))
+    }
+
+    fn is_precompile(&self, address: H160, _remaining_gas: u64) -> IsPrecompileResult {
+        IsPrecompileResult::Answer {
+            is_precompile: address == multivm_call_address(),
+            extra_cost: 0,
+        }
+    }
+}
+
+fn multivm_call_address() -> H160 {
+    H160::from_low_u64_be(MULTIVM_CALL_PRECOMPILE)
+}
+
+/// Schedules a MultiVM cross-contract call, made once the EVM execution succeeds.
+/// Input is ABI encoded `(string account_id, string method, bytes borsh_args)`.
+/// The call is kept as a log of the precompile, so it's dropped together with the reverted
+/// frame, and its response isn't returned to the EVM caller
+fn multivm_call(handle: &mut impl PrecompileHandle) -> PrecompileResult {
+    if handle.is_static() {
+        return Err(revert("MultiVM call is not allowed in static context"));
+    }
+    handle
+        .record_cost(MULTIVM_CALL_COST + MULTIVM_CALL_GAS)
+        .map_err(|exit_status| PrecompileFailure::Error { exit_status })?;
+
+    let input = handle.input().to_vec();
+    let Some((contract_id, _, _)) = decode_multivm_call(&input) else {
+        return Err(revert("Invalid MultiVM call input"));
+    };
+    // Calls of EVM transactions are made by the system meta-contract, it must not call itself
+    if contract_id == AccountId::system_meta_contract() {
+        return Err(revert(
+            "MultiVM call to the system meta-contract is not allowed",
+        ));
+    }
+
+    handle
+        .log(multivm_call_address(), vec![], input)
+        .map_err(|exit_status| PrecompileFailure::Error { exit_status })?;
+
+    Ok(PrecompileOutput {
+        exit_status: ExitSucceed::Returned,
+        output: vec![],
+    })
+}
+
+fn decode_multivm_call(input: &[u8]) -> Option<(AccountId, String, Vec<u8>)> {
+    let tokens = ethers_core::abi::decode(
+        &[ParamType::String, ParamType::String, ParamType::Bytes],
+        input,
+    )
+    .ok()?;
+    let [Token::String(account_id), Token::String(method), Token::Bytes(args)] = tokens.as_slice()
+    else {
+        return None;
+    };
+    let contract_id = MultiVmAccountId::try_from(account_id.as_str()).ok()?;
+
+    Some((contract_id.into(), method.clone(), args.clone()))
+}
+
+/// Builds revert with Solidity `Error(string)` encoded reason
+fn revert(message: &str) -> PrecompileFailure {
+    let reason = Token::String(message.to_string());
+    let mut output = ethers_core::utils::id("Error(string)").to_vec();
+    output.extend(ethers_core::abi::encode(&[reason]));
+
+    PrecompileFailure::Revert {
+        exit_status: ExitRevert::Reverted,
+        output,
+    }
+}
+
+/// Emits the logs of the applied execution and makes the MultiVM calls scheduled by its
+/// frames, in order. Returns the reserved gas the calls didn't use
+fn process_logs(logs: Vec<EvmLog>) -> u64 {
+    let precompile_address = EvmAddress::from(multivm_call_address());
+    let (calls, logs): (Vec<_>, Vec<_>) = logs
+        .into_iter()
+        .partition(|log| log.address == precompile_address);
+    system_env::emit_logs(logs);
+
+    calls
+        .into_iter()
+        .map(|call| {
+            let (contract_id, method, args) =
+                decode_multivm_call(&call.data).expect("MultiVM call is corrupted");
+            // Failed call doesn't revert the EVM execution, its commitment keeps the error
+            system_env::cross_contract_call_raw(contract_id, method, MULTIVM_CALL_GAS, 0, args);
+            MULTIVM_CALL_GAS - system_env::last_call_cycles().min(MULTIVM_CALL_GAS)
+        })
+        .sum()
+}
+
+/// Charges the caller for the gas used by the execution
+pub fn charge_gas_fee(caller: H160, gas_used: u64, gas_price: u128) {
+    let fee = (gas_used as u128)
+        .checked_mul(gas_price)
+        .expect("Gas fee overflow");
+    if fee == 0 {
+        return;
+    }
+
+    let account =
+        account_management::account(&EvmAddress::from(caller).into()).expect("Caller not found"); // TODO: handle error
+    if let Err(error) = account_management::pay_fee(account, fee) {
+        panic!("Failed to pay {} for gas: {}", fee, error); // TODO: handle error
+    }
+}
+
+/// Charges the caller the creation fee of accounts created by the execution
+fn charge_account_creation(caller: H160, created_accounts: u128) {
+    if created_accounts == 0 {
+        return;
+    }
+
+    let fee = account_management::account_creation_params().creation_fee * created_accounts;
+    let account =
+        account_management::account(&EvmAddress::from(caller).into()).expect("Caller not found"); // TODO: handle error
+    if let Err(error) = account_management::pay_fee(account, fee) {
+        panic!("Failed to pay {} for account creation: {}", fee, error); // TODO: handle error
+    }
+}
+
 use borsh::{BorshDeserialize, BorshSerialize};
 use evm::backend::{Apply, Backend, Basic, Log};
 
@@ -102,6 +272,7 @@ use evm::backend::{Apply, Backend, Basic, Log};
 pub struct EvmMemoryBackend<'vicinity> {
     vicinity: &'vicinity MemoryVicinity,
     logs: Vec<Log>,
+    created_accounts: u128,
 }
 
 impl<'vicinity> EvmMemoryBackend<'vicinity> {
@@ -109,8 +280,26 @@ impl<'vicinity> EvmMemoryBackend<'vicinity> {
         Self {
             vicinity,
             logs: Vec::new(),
+            created_accounts: 0,
         }
     }
+
+    /// Returns the number of accounts created by applied changes
+    pub fn created_accounts(&self) -> u128 {
+        self.created_accounts
+    }
+
+    /// Returns logs collected by applied changes
+    pub fn logs(&self) -> Vec<EvmLog> {
+        self.logs
+            .iter()
+            .map(|log| EvmLog {
+                address: log.address.into(),
+                topics: log.topics.iter().map(|topic| topic.0).collect(),
+                data: log.data.clone(),
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Default, BorshDeserialize, BorshSerialize)]
@@ -138,7 +327,95 @@ impl From<EvmBasic> for Basic {
 }
 
 const CODE_KEY: &str = "evm_code";
+/// Prefix of the storage slot keys, each slot is stored and recorded in history separately
 const STORAGE_KEY: &str = "evm_storage";
+/// Indexes of non-zero storage slots, changed only when slots are added or cleared
+const STORAGE_SLOTS_KEY: &str = "evm_storage_slots";
+
+fn slot_key(index: &H256) -> String {
+    format!("{}.{:x}", STORAGE_KEY, index)
+}
+
+fn storage_slots(account_id: &AccountId) -> BTreeSet<[u8; 32]> {
+    account_management::account_storage(account_id, STORAGE_SLOTS_KEY.into()).unwrap_or_default()
+}
+
+/// Storage written before slots were stored separately, the whole map under the prefix key.
+/// It is moved to slots by the next storage update of the account
+fn legacy_storage(account_id: &AccountId) -> Option<BTreeMap<H256, H256>> {
+    account_management::account_storage::<Vec<u8>>(account_id, STORAGE_KEY.into())
+        .map(|bytes| bincode::deserialize(&bytes).unwrap())
+}
+
+/// Returns EVM code of the account, empty if there is no code
+pub fn code(address: H160) -> Vec<u8> {
+    account_management::account_storage(&EvmAddress::from(address).into(), CODE_KEY.into())
+        .unwrap_or_default()
+}
+
+/// Returns EVM storage value of the account at the given index
+pub fn storage(address: H160, index: H256) -> H256 {
+    let account_id = EvmAddress::from(address).into();
+    if let Some(value) =
+        account_management::account_storage::<[u8; 32]>(&account_id, slot_key(&index))
+    {
+        return value.into();
+    }
+
+    // Accounts created by nested CREATE/CREATE2 or plain EOAs may have no storage yet
+    legacy_storage(&account_id)
+        .and_then(|storage| storage.get(&index).cloned())
+        .unwrap_or_default()
+}
+
+/// Writes changed slots of the account, zero values clear them. Reset clears all slots first
+fn update_storage(
+    account_id: &AccountId,
+    changes: impl IntoIterator<Item = (H256, H256)>,
+    reset: bool,
+) {
+    let mut slots = storage_slots(account_id);
+    let mut slots_changed = false;
+
+    if let Some(legacy) = legacy_storage(account_id) {
+        account_management::delete_account_storage(account_id, STORAGE_KEY.into());
+        if !reset {
+            for (index, value) in legacy.into_iter().filter(|(_, value)| !value.is_zero()) {
+                slots.insert(index.0);
+                account_management::update_account_storage(account_id, slot_key(&index), value.0);
+            }
+        }
+        slots_changed = true;
+    }
+
+    if reset {
+        for index in std::mem::take(&mut slots) {
+            account_management::delete_account_storage(account_id, slot_key(&index.into()));
+            slots_changed = true;
+        }
+    }
+
+    for (index, value) in changes {
+        if value.is_zero() {
+            if slots.remove(&index.0) {
+                account_management::delete_account_storage(account_id, slot_key(&index));
+                slots_changed = true;
+            }
+        } else {
+            slots_changed |= slots.insert(index.0);
+            account_management::update_account_storage(account_id, slot_key(&index), value.0);
+        }
+    }
+
+    if !slots_changed {
+        return;
+    }
+    if slots.is_empty() {
+        account_management::delete_account_storage(account_id, STORAGE_SLOTS_KEY.into());
+    } else {
+        account_management::update_account_storage(account_id, STORAGE_SLOTS_KEY.into(), slots);
+    }
+}
 
 impl<'vicinity> Backend for EvmMemoryBackend<'vicinity> {
     fn gas_price(&self) -> U256 {
@@ -188,9 +465,10 @@ impl<'vicinity> Backend for EvmMemoryBackend<'vicinity> {
         account_management::account_exists(&EvmAddress::from(address).into())
     }
 
+    /// Balance locked for the account storage is out of EVM reach
     fn basic(&self, address: H160) -> Basic {
         let (balance, nonce) = match account_management::account(&EvmAddress::from(address).into())
-            .map(|account| (account.balance.into(), account.nonce.into()))
+            .map(|account| (account.available_balance().into(), account.nonce.into()))
         {
             Some((balance, nonce)) => (Some(balance), Some(nonce)),
             None => (None, None),
@@ -203,20 +481,11 @@ impl<'vicinity> Backend for EvmMemoryBackend<'vicinity> {
     }
 
     fn code(&self, address: H160) -> Vec<u8> {
-        account_management::account_storage(&EvmAddress::from(address).into(), CODE_KEY.into())
-            .unwrap_or_default()
+        code(address)
     }
 
     fn storage(&self, address: H160, index: H256) -> H256 {
-        let bytes: Vec<u8> = account_management::account_storage(
-            &EvmAddress::from(address).into(),
-            STORAGE_KEY.into(),
-        )
-        .unwrap_or_default();
-
-        let storage: BTreeMap<H256, H256> = bincode::deserialize(&bytes).unwrap();
-
-        storage.get(&index).cloned().unwrap_or_default()
+        storage(address, index)
     }
 
     fn original_storage(&self, address: H160, index: H256) -> Option<H256> {
@@ -240,13 +509,24 @@ impl<'vicinity> ApplyBackend for EvmMemoryBackend<'vicinity> {
                     storage: new_storage,
                     reset_storage,
                 } => {
+                    let exists =
+                        account_management::account_exists(&EvmAddress::from(address).into());
+                    // Touched addresses get no account, unless they receive value or code
+                    if !exists && basic.balance.is_zero() && basic.nonce.is_zero() && code.is_none()
+                    {
+                        continue;
+                    }
+                    if !exists {
+                        account_management::Account::try_create(None, address.into());
+                        self.created_accounts += 1;
+                    }
+
                     let is_empty = {
                         let mut account =
-                            account_management::account(&EvmAddress::from(address).into())
-                                .unwrap_or_else(|| {
-                                    account_management::Account::try_create(None, address.into())
-                                });
-                        account.balance = basic.balance.as_u128();
+                            account_management::account(&EvmAddress::from(address).into()).unwrap();
+                        // EVM saw the available balance only, the locked part stays
+                        let locked = account.balance - account.available_balance();
+                        account.balance = basic.balance.as_u128() + locked;
                         account.nonce = basic.nonce.as_u64();
 
                         if let Some(code) = code {
@@ -259,67 +539,29 @@ impl<'vicinity> ApplyBackend for EvmMemoryBackend<'vicinity> {
                             account.executable = Some(Executable::Evm());
                         }
 
-                        account_management::update_account(account);
-
-                        if reset_storage {
-                            let empty_state = BTreeMap::<H256, H256>::new();
-                            account_management::update_account_storage(
-                                &EvmAddress::from(address).into(),
-                                STORAGE_KEY.into(),
-                                bincode::serialize(&empty_state).unwrap(),
-                            );
-                        }
-
-                        let mut storage: BTreeMap<H256, H256> =
-                            account_management::account_storage::<Vec<u8>>(
-                                &EvmAddress::from(address).into(),
-                                STORAGE_KEY.into(),
-                            )
-                            .map(|bytes| bincode::deserialize(&bytes).unwrap())
-                            .unwrap_or_default();
-
-                        let zeros = storage
-                            .iter()
-                            .filter(|(_, v)| v == &&H256::default())
-                            .map(|(k, _)| *k)
-                            .collect::<Vec<H256>>();
-
-                        for zero in zeros {
-                            storage.remove(&zero);
-                        }
+                        // Accounts with MultiVM alias are managed by the system, not by EVM state
+                        let is_empty = account.balance == 0
+                            && account.nonce == 0
+                            && account.executable.is_none()
+                            && account.multivm_account_id.is_none();
 
-                        for (index, value) in new_storage {
-                            if value == H256::default() {
-                                storage.remove(&index);
-                            } else {
-                                storage.insert(index, value);
-                            }
-                        }
-
-                        account_management::update_account_storage(
+                        account_management::update_account(account);
+                        update_storage(
                             &EvmAddress::from(address).into(),
-                            STORAGE_KEY.into(),
-                            bincode::serialize(&storage).unwrap(),
+                            new_storage,
+                            reset_storage,
                         );
 
-                        // TODO
-                        // account.balance == U256::zero()
-                        //     && account.nonce == U256::zero()
-                        //     && account.code.is_empty()
-                        false
+                        is_empty
                     };
 
                     if is_empty && delete_empty {
-                        // TODO
-                        // self.state.remove(&address);
-                        unimplemented!("delete_empty");
+                        delete_account(address);
                     }
                 }
-                Apply::Delete { address: _ } => {
-                    // TODO
-                    // self.state.remove(&address);
-                    unimplemented!("delete");
-                }
+                // Emitted on SELFDESTRUCT. Under the Istanbul rules the executor runs with,
+                // any contract is destroyed with its code and storage, not only the new ones
+                Apply::Delete { address } => delete_account(address),
             }
         }
 
@@ -328,3 +570,16 @@ impl<'vicinity> ApplyBackend for EvmMemoryBackend<'vicinity> {
         }
     }
 }
+
+/// Removes EVM account with its code and storage,
+/// the balance is already moved to the beneficiary by the executor
+fn delete_account(address: H160) {
+    let account_id = EvmAddress::from(address).into();
+    if !account_management::account_exists(&account_id) {
+        return;
+    }
+
+    account_management::delete_account_storage(&account_id, CODE_KEY.into());
+    update_storage(&account_id, [], true);
+    account_management::delete_account(&account_id);
+}
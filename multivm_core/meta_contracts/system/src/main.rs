@@ -5,23 +5,55 @@ use std::io::Read;
 
 use account_management::{update_account, MultiVmExecutable};
 use borsh::{BorshDeserialize, BorshSerialize};
-use ethers_core::types::NameOrAddress;
+use ethers_core::types::{transaction::eip2718::TypedTransaction, NameOrAddress};
 use multivm_primitives::{
-    AccountId, ContractCall, ContractCallContext, EnvironmentContext, EthereumTransactionRequest,
-    EvmAddress, MultiVmAccountId, SignedTransaction, SupportedTransaction,
+    image_id_hex, solidity_abi, AccessKey, AccessKeyPermission, AccountCreationParams, AccountId,
+    Commitment, ContractCall, ContractCallContext, ContractResponse, DelegatedTransaction,
+    EnvironmentContext, EthereumTransactionRequest, EvmAddress, MultiVmAccountId, Multisig,
+    SignedTransaction, StorageUsageUpdate, SupportedTransaction, Unbonding, BASE_FEE_PER_GAS,
+    CHAIN_ID, ONE_TOKEN, UNBONDING_PERIOD,
 };
 
 use crate::account_management::Executable;
 
 mod evm;
 mod system_env;
+#[cfg(feature = "tracing")]
+mod tracer;
 
-const TOKEN_DECIMALS: u32 = 18;
-const ONE_TOKEN: u128 = 10u128.pow(TOKEN_DECIMALS);
+/// Guest built without the `tracing` feature can't trace, the node requests traces in debug mode only
+#[cfg(not(feature = "tracing"))]
+mod tracer {
+    use multivm_primitives::EvmTrace;
+
+    pub fn enable() {
+        panic!("Tracing is not supported by this build"); // TODO: handle error
+    }
+
+    pub fn trace<T>(execute: impl FnOnce() -> T) -> (T, Option<Tracer>) {
+        (execute(), None)
+    }
+
+    pub enum Tracer {}
+
+    impl Tracer {
+        pub fn finish(self, _gas_used: u64, _reason: &evm::ExitReason, _output: &[u8]) -> EvmTrace {
+            match self {}
+        }
+    }
+}
+
+/// Intrinsic gas of the plain value transfer
+const TRANSFER_GAS: u64 = 21_000;
+/// Gas limit of EVM views, the same as the default RPC gas cap of geth
+const VIEW_GAS_LIMIT: u64 = 50_000_000;
+/// Validator commission of 100%, in basis points
+const MAX_COMMISSION: u16 = 10_000;
 
 #[derive(BorshDeserialize, BorshSerialize, Debug)]
 struct AccountCreationRequest {
-    pub account_id: MultiVmAccountId,
+    /// Implicit accounts have the EVM address only
+    pub account_id: Option<MultiVmAccountId>,
     pub address: EvmAddress,
 }
 
@@ -30,6 +62,27 @@ struct ContractDeploymentArgs {
     pub image_id: [u32; 8],
 }
 
+#[derive(BorshDeserialize, BorshSerialize)]
+struct SubContractCreationRequest {
+    pub account_id: AccountId,
+    pub image_id: [u32; 8],
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ContractUpgradeArgs {
+    pub image_id: [u32; 8],
+    /// Call of the new code migrating the contract storage
+    pub migration: Option<ContractCall>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct StakeArgs {
+    pub amount: u128,
+    pub public_key: Vec<u8>,
+    /// In basis points
+    pub commission: u16,
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
 struct EvmCall {
     pub from: Option<[u8; 20]>,
@@ -49,6 +102,10 @@ enum Action {
     View(SupportedView, EnvironmentContext),
     Call(ContractCallContext),
     EvmCall(ContractCallContext),
+    /// Same as `ExecuteTransaction`, with EVM execution traced. Non-proving debug mode only
+    TraceTransaction(SupportedTransaction, EnvironmentContext),
+    /// Same as `View`, with EVM execution traced
+    TraceView(SupportedView, EnvironmentContext),
 }
 risc0_zkvm::entry!(entrypoint);
 
@@ -61,14 +118,8 @@ fn entrypoint() {
     let action: Action = BorshDeserialize::try_from_slice(&mut bytes).expect("Corrupted action");
 
     match action {
-        Action::ExecuteTransaction(tx, environment) => match tx {
-            SupportedTransaction::MultiVm(tx) => process_transaction(tx, environment),
-            SupportedTransaction::Evm(tx) => process_ethereum_transaction(tx, environment),
-        },
-        Action::View(v, environment) => match v {
-            SupportedView::MultiVm(context) => view(context),
-            SupportedView::Evm(call) => evm_view_call(call, environment),
-        },
+        Action::ExecuteTransaction(tx, environment) => execute_transaction(tx, environment),
+        Action::View(v, environment) => execute_view(v, environment),
         Action::Call(ctx) => {
             system_env::setup_env(&ctx);
             process_call(ctx.clone().contract_id, ctx.clone().contract_call, ctx)
@@ -77,20 +128,58 @@ fn entrypoint() {
             system_env::setup_env(&ctx);
             evm_call(ctx)
         }
+        Action::TraceTransaction(tx, environment) => {
+            tracer::enable();
+            execute_transaction(tx, environment)
+        }
+        Action::TraceView(v, environment) => {
+            tracer::enable();
+            execute_view(v, environment)
+        }
     };
 }
 
+fn execute_transaction(tx: SupportedTransaction, environment: EnvironmentContext) {
+    match tx {
+        SupportedTransaction::MultiVm(tx) => process_transaction(tx, environment),
+        SupportedTransaction::Evm(tx) => process_ethereum_transaction(tx, environment),
+        SupportedTransaction::Delegated(tx) => process_delegated_transaction(tx, environment),
+    }
+}
+
+fn execute_view(v: SupportedView, environment: EnvironmentContext) {
+    match v {
+        SupportedView::MultiVm(context) => view(context),
+        SupportedView::Evm(call) => evm_view_call(call, environment),
+    }
+}
+
 fn process_ethereum_transaction(tx: EthereumTransactionRequest, environment: EnvironmentContext) {
-    let (tx, sign) = tx.decode();
-    if !sign.verify(tx.sighash(), tx.from.unwrap()).is_ok() {
+    let (tx, sign) = tx.decode().expect("Invalid transaction encoding"); // TODO: handle error
+    let from = *tx.from().expect("no 'from', probably tx is not signed");
+    if !sign.verify(tx.sighash(), from).is_ok() {
         panic!("Invalid signature");
     }
+    // EIP-155: transactions without a chain id or signed for another chain could be replayed here
+    if tx.chain_id().map(|id| id.as_u64()) != Some(CHAIN_ID) {
+        panic!("Invalid chain id: {:?}", tx.chain_id()); // TODO: handle error
+    }
+
+    let gas_price = EthereumTransactionRequest::effective_gas_price(&tx, BASE_FEE_PER_GAS)
+        .expect("Gas price is lower than base fee"); // TODO: handle error
+    let gas_limit: u64 = tx
+        .gas()
+        .and_then(|gas| gas.try_into().ok())
+        .expect("Invalid gas limit"); // TODO: handle error
+    if gas_limit < TRANSFER_GAS {
+        panic!("Gas limit {} is lower than intrinsic gas", gas_limit); // TODO: handle error
+    }
 
     let contract_call = ContractCall {
         method: "".to_string(),
         args: vec![],
-        gas: 300_000,
-        deposit: tx.value.unwrap_or_default().try_into().unwrap(),
+        gas: gas_limit,
+        deposit: tx.value().cloned().unwrap_or_default().try_into().unwrap(),
     };
     let ctx = ContractCallContext {
         contract_id: AccountId::system_meta_contract(),
@@ -102,35 +191,122 @@ fn process_ethereum_transaction(tx: EthereumTransactionRequest, environment: Env
     };
     system_env::setup_env(&ctx);
 
-    let caller = account_management::account(
-        &EvmAddress::from(tx.from.expect("no 'from', probably tx is not signed")).into(),
-    )
-    .expect(format!("Caller not found: {:#?}", tx.from).as_str()); // TODO: handle error
+    let mut caller = account_management::account(&EvmAddress::from(from).into())
+        .expect(format!("Caller not found: {:#?}", from).as_str()); // TODO: handle error
+    check_evm_sender(from.into());
+
+    let nonce = tx.nonce().cloned().unwrap_or_default();
+    if nonce != caller.nonce.into() {
+        panic!(
+            "Invalid nonce: expected {}, got {}", // TODO: handle error
+            caller.nonce, nonce
+        );
+    }
+    // The whole gas limit has to be affordable, only the used gas is charged
+    let max_cost = (gas_limit as u128)
+        .checked_mul(gas_price)
+        .and_then(|fee| fee.checked_add(ctx.contract_call.deposit))
+        .expect("Transaction cost overflow"); // TODO: handle error
+    if caller.available_balance() < max_cost {
+        panic!(
+            "Insufficient funds: balance {}, required {}", // TODO: handle error
+            caller.available_balance(),
+            max_cost
+        );
+    }
 
     match tx.processing_flow() {
-        EthereumTxFlow::Deploy(bytecode) => evm::deploy_evm_contract(caller, bytecode),
+        EthereumTxFlow::Deploy(bytecode) => {
+            evm::deploy_evm_contract(caller, bytecode, gas_limit, gas_price)
+        }
         EthereumTxFlow::Call(contract_id, data) => {
-            let contract = account_management::account(&contract_id.clone().into()).unwrap();
+            let value = ctx.contract_call.deposit;
+            let contract = match account_management::account(&contract_id.clone().into()) {
+                Some(contract) => contract,
+                // Only value transfers create accounts, the sender pays for the creation
+                None if value > 0 => {
+                    let params = account_management::account_creation_params();
+                    account_management::pay_fee(caller, params.creation_fee)
+                        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+
+                    // Reloaded, the caller may be the fee collector
+                    caller = account_management::account(&EvmAddress::from(from).into()).unwrap();
+                    account_management::Account::try_create(None, contract_id.clone())
+                }
+                None => {
+                    caller.nonce += 1;
+                    update_account(caller);
+                    evm::charge_gas_fee(from, TRANSFER_GAS, gas_price);
+                    system_env::commit(());
+                    return;
+                }
+            };
             match contract.executable {
                 Some(Executable::Evm()) => evm::call_contract(
                     caller.evm_address,
                     contract_id,
                     data,
-                    ctx.contract_call.deposit,
+                    value,
+                    gas_limit,
+                    gas_price,
                     true,
                 ),
                 Some(Executable::MultiVm(_)) => {
                     let Some(multivm_contract_id) = contract.multivm_account_id else {
                         panic!("Contract is MultiVM executable but has no multivm account");
                     };
-                    process_call(
-                        multivm_contract_id.into(),
-                        borsh::from_slice(&data)
-                            .expect("multivm tx data was incorrectly serialized"),
-                        ctx,
-                    )
+                    // EVM executor bumps the nonce by itself, here it has to be done manually
+                    caller.nonce += 1;
+                    // Attached value goes to the contract and is passed as the call deposit
+                    account_management::transfer(caller, multivm_contract_id.clone().into(), value)
+                        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+
+                    let abi: Option<Vec<String>> = account_management::account_storage(
+                        &multivm_contract_id.clone().into(),
+                        solidity_abi::ABI_KEY.into(),
+                    );
+                    match abi {
+                        Some(abi) => {
+                            let response = abi_contract_call(
+                                multivm_contract_id.into(),
+                                EvmAddress::from(from).into(),
+                                abi,
+                                data,
+                                gas_limit,
+                                value,
+                            );
+                            charge_multivm_call_gas(from, gas_limit, gas_price);
+                            system_env::commit(response)
+                        }
+                        None => {
+                            let call: ContractCall = borsh::from_slice(&data)
+                                .expect("multivm tx data was incorrectly serialized");
+                            let commitment = system_env::cross_contract_call_raw(
+                                multivm_contract_id.clone().into(),
+                                call.method,
+                                gas_limit,
+                                value,
+                                call.args,
+                            );
+                            refund_failed_deposit(
+                                &commitment,
+                                multivm_contract_id.into(),
+                                EvmAddress::from(from).into(),
+                                value,
+                            );
+                            charge_multivm_call_gas(from, gas_limit, gas_price);
+                            system_env::commit(commitment)
+                        }
+                    }
+                }
+                None => {
+                    // Plain value transfer to the account without code
+                    caller.nonce += 1;
+                    account_management::transfer(caller, contract_id.into(), value)
+                        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+                    evm::charge_gas_fee(from, TRANSFER_GAS, gas_price);
+                    system_env::commit(());
                 }
-                _ => panic!("Executable not supported"),
             }
         }
     };
@@ -145,17 +321,15 @@ trait TransactionFlow<T> {
     fn processing_flow(self) -> T;
 }
 
-impl TransactionFlow<EthereumTxFlow>
-    for ethers_core::types::transaction::request::TransactionRequest
-{
+impl TransactionFlow<EthereumTxFlow> for TypedTransaction {
     fn processing_flow(self) -> EthereumTxFlow {
-        let receiver_id: EvmAddress = match self.to {
-            None => return EthereumTxFlow::Deploy(self.data.unwrap().to_vec()),
-            Some(NameOrAddress::Address(address)) => address.into(),
+        let receiver_id: EvmAddress = match self.to() {
+            None => return EthereumTxFlow::Deploy(self.data().unwrap().to_vec()),
+            Some(NameOrAddress::Address(address)) => (*address).into(),
             _ => panic!("Not supported"),
         };
 
-        let data = match self.data {
+        let data = match self.data() {
             None => vec![],
             Some(data) => data.to_vec(),
         };
@@ -169,10 +343,33 @@ fn view(context: ContractCallContext) {
 
     match context.contract_call.method.as_str() {
         "account_info" => account_info(context),
+        "access_keys" => access_keys(context),
+        "multisig" => multisig(context),
+        "code_history" => code_history(context),
+        "is_immutable" => is_immutable(context),
+        "stored_code" => stored_code(context),
+        "total_supply" => total_supply(context),
+        "validators" => validators(context),
+        "epoch_validators" => epoch_validators(context),
+        "unbonding" => unbonding(context),
+        "evm_code" => evm_code(context),
+        "evm_storage" => evm_storage(context),
         _ => panic!("Method not found"),
     }
 }
 
+fn evm_code(context: ContractCallContext) {
+    let address: EvmAddress = context.contract_call.try_deserialize_args().unwrap();
+    system_env::commit(evm::code(address.into()))
+}
+
+fn evm_storage(context: ContractCallContext) {
+    let (address, index): (EvmAddress, [u8; 32]) =
+        context.contract_call.try_deserialize_args().unwrap();
+    let value = evm::storage(address.into(), index.into());
+    system_env::commit(value.to_fixed_bytes())
+}
+
 fn evm_view_call(call: EvmCall, environment: EnvironmentContext) {
     let contract_call = ContractCall {
         method: "".to_string(),
@@ -199,6 +396,8 @@ fn evm_view_call(call: EvmCall, environment: EnvironmentContext) {
         contract_address,
         call.input,
         0,
+        VIEW_GAS_LIMIT,
+        0,
         false,
     )
 }
@@ -214,17 +413,24 @@ fn evm_call(ctx: ContractCallContext) {
         contract.evm_address,
         ctx.contract_call.args,
         ctx.contract_call.deposit,
+        ctx.contract_call.gas,
+        0,
         true,
     )
 }
 
 // TODO: remove this
 fn init_debug_account(address: EvmAddress) {
+    // Called by the node at genesis only, transactions can't be signed by the system account
+    if system_env::signer() != AccountId::system_meta_contract() {
+        panic!("Genesis only method"); // TODO: handle error
+    }
+
     let mut account = account_management::Account::try_create(
         Some(MultiVmAccountId::try_from("super.multivm").unwrap()),
         address,
     );
-    account.balance = 1_000_000_000_000 * ONE_TOKEN;
+    account_management::mint(&mut account, 1_000_000_000_000 * ONE_TOKEN);
     update_account(account);
     system_env::commit(());
 }
@@ -234,23 +440,13 @@ fn process_transaction(signed_tx: SignedTransaction, environment: EnvironmentCon
 
     system_env::setup_env(&ctx);
 
-    // Skip signature verification for debug purposes
-    // TODO: remove
-    if ctx.environment.block_height > 1 {
-        let signer_id = system_env::signer();
-
-        let signer = account_management::account(&signer_id)
-            .expect(&format!("Signer not found: {}", signer_id)); // TODO: handle error
-
-        if !signed_tx.verify(signer.evm_address) {
-            panic!("Invalid signature"); // TODO: handle error
-        }
-    }
+    check_signatures(&signed_tx);
 
     let SignedTransaction {
         transaction: tx,
         signature: _,
         recovery_id: _,
+        cosignatures: _,
         attachments: _,
     } = signed_tx;
 
@@ -259,12 +455,204 @@ fn process_transaction(signed_tx: SignedTransaction, environment: EnvironmentCon
     }
 }
 
+/// Executes the user signed transaction relayed by another account, which pays for gas
+fn process_delegated_transaction(
+    delegated_tx: DelegatedTransaction,
+    environment: EnvironmentContext,
+) {
+    let ctx = delegated_tx
+        .transaction
+        .transaction
+        .context(0, environment.clone());
+
+    system_env::setup_env(&ctx);
+
+    check_relayer(&delegated_tx);
+    check_signatures(&delegated_tx.transaction);
+
+    let DelegatedTransaction {
+        transaction: SignedTransaction {
+            transaction: tx, ..
+        },
+        relayer_id,
+        signature: _,
+        recovery_id: _,
+    } = delegated_tx;
+
+    system_env::set_fee_payer(relayer_id);
+
+    for call in tx.calls {
+        process_call(tx.receiver_id.clone(), call, ctx.clone());
+    }
+}
+
+/// Verifies the transaction is authorized by the signer account keys
+fn check_signatures(signed_tx: &SignedTransaction) {
+    let signer_id = &signed_tx.transaction.signer_id;
+
+    if !account_management::account_exists(signer_id) {
+        panic!("Signer not found: {}", signer_id); // TODO: handle error
+    }
+
+    match account_management::multisig(signer_id) {
+        Some(multisig) => check_multisig(signed_tx, &multisig),
+        None => check_access_key(signed_tx, signer_id),
+    }
+
+    // The same transaction can't be executed, or relayed, twice
+    let mut signer = account_management::account(signer_id).unwrap();
+    if signed_tx.transaction.nonce != signer.nonce {
+        panic!(
+            "Invalid nonce: account nonce is {}, got {}",
+            signer.nonce, signed_tx.transaction.nonce
+        ); // TODO: handle error
+    }
+    signer.nonce += 1;
+    update_account(signer);
+}
+
+/// Verifies the delegated transaction is signed by one of the relayer full access keys
+fn check_relayer(delegated_tx: &DelegatedTransaction) {
+    let relayer_id = &delegated_tx.relayer_id;
+
+    if !account_management::account_exists(relayer_id) {
+        panic!("Relayer not found: {}", relayer_id); // TODO: handle error
+    }
+    if account_management::multisig(relayer_id).is_some() {
+        panic!("Multisig accounts can't relay transactions"); // TODO: handle error
+    }
+
+    let public_key = delegated_tx
+        .recover_relayer()
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+    let address = EvmAddress::from(public_key);
+
+    let is_full_access = account_management::access_keys(relayer_id)
+        .iter()
+        .any(|key| {
+            key.address == address && matches!(key.permission, AccessKeyPermission::FullAccess)
+        });
+    if !is_full_access {
+        panic!(
+            "Full access key {} not found for relayer {}",
+            address, relayer_id
+        ); // TODO: handle error
+    }
+}
+
+/// Verifies the transaction is signed by one of the signer access keys,
+/// and charges gas allowance of the function call key
+fn check_access_key(signed_tx: &SignedTransaction, signer_id: &AccountId) {
+    let public_key = signed_tx
+        .recover()
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+    let address = EvmAddress::from(public_key);
+
+    if let Err(error) = signed_tx.verify(address.clone()) {
+        panic!("{}", error); // TODO: handle error
+    }
+
+    let mut keys = account_management::access_keys(signer_id);
+    let key = keys
+        .iter_mut()
+        .find(|key| key.address == address)
+        .unwrap_or_else(|| panic!("Access key {} not found for {}", address, signer_id)); // TODO: handle error
+
+    let AccessKeyPermission::FunctionCall {
+        contract_id,
+        methods,
+        gas_allowance,
+    } = &mut key.permission
+    else {
+        return;
+    };
+
+    let tx = &signed_tx.transaction;
+    for call in &tx.calls {
+        if tx.receiver_id != *contract_id
+            || (!methods.is_empty() && !methods.contains(&call.method))
+        {
+            panic!(
+                "Access key doesn't allow {}.{}",
+                tx.receiver_id, call.method
+            ); // TODO: handle error
+        }
+        if call.deposit != 0 {
+            panic!("Access key doesn't allow deposits"); // TODO: handle error
+        }
+
+        *gas_allowance = gas_allowance
+            .checked_sub(call.gas)
+            .expect("Access key gas allowance exceeded"); // TODO: handle error
+    }
+
+    account_management::update_access_keys(signer_id, keys);
+}
+
+/// EVM transactions are signed by the account alias key, it has to remain a full access key,
+/// so rotated or removed keys lose control of the account. A single signature can't approve
+/// multisig transactions
+fn check_evm_sender(address: EvmAddress) {
+    let account_id: AccountId = address.clone().into();
+    if account_management::multisig(&account_id).is_some() {
+        panic!("Multisig accounts can't send EVM transactions"); // TODO: handle error
+    }
+
+    let is_full_access = account_management::access_keys(&account_id)
+        .iter()
+        .any(|key| {
+            key.address == address && matches!(key.permission, AccessKeyPermission::FullAccess)
+        });
+    if !is_full_access {
+        panic!("{} is not a full access key of its account", address); // TODO: handle error
+    }
+}
+
+/// Verifies the transaction is signed by at least threshold distinct keys of the multisig
+fn check_multisig(signed_tx: &SignedTransaction, multisig: &Multisig) {
+    let mut signers = signed_tx
+        .signers()
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+    signers.sort();
+    signers.dedup();
+
+    let approvals = signers
+        .iter()
+        .filter(|signer| multisig.keys.contains(signer))
+        .count();
+    if approvals < multisig.threshold as usize {
+        panic!(
+            "Not enough multisig signatures: {} of {}",
+            approvals, multisig.threshold
+        ); // TODO: handle error
+    }
+}
+
 fn process_call(contract_id: AccountId, call: ContractCall, ctx: ContractCallContext) {
     if contract_id == AccountId::system_meta_contract() {
         match call.method.as_str() {
             "create_account" => create_account(call),
+            "add_key" => add_key(call),
+            "remove_key" => remove_key(call),
+            "rotate_key" => rotate_key(call),
+            "set_multisig" => set_multisig(call),
+            "remove_multisig" => remove_multisig(),
             "deploy_contract" => deploy_multivm_contract(call),
+            "upgrade_contract" => upgrade_multivm_contract(call),
+            "upload_code" => upload_code(call),
+            "deploy_from_image" => deploy_from_image(call),
+            "create_sub_contract" => create_sub_contract(call),
+            "make_immutable" => make_immutable(),
+            "declare_abi" => declare_abi(call),
             "init_debug_account" => init_debug_account(call.try_deserialize_args().unwrap()),
+            "set_registrar" => set_registrar(call),
+            "set_account_creation_params" => set_account_creation_params(call),
+            "set_fee_collector" => set_fee_collector(call),
+            "update_storage_usage" => update_storage_usage(call),
+            "stake" => stake(call),
+            "unstake" => unstake(call),
+            "withdraw" => withdraw(),
+            "snapshot_validators" => snapshot_validators(call),
             "account_info" => account_info(ctx),
             "transfer" => transfer(ctx),
             _ => panic!("Method not found"),
@@ -276,23 +664,319 @@ fn process_call(contract_id: AccountId, call: ContractCall, ctx: ContractCallCon
 
 fn create_account(call: ContractCall) {
     let req: AccountCreationRequest = call.try_deserialize_args().unwrap();
+    let creator_id = system_env::caller();
 
-    let mut account = account_management::Account::try_create(Some(req.account_id), req.address);
-    let caller = account_management::account(&system_env::caller()).unwrap();
+    if let Some(account_id) = &req.account_id {
+        check_account_creator(account_id, &creator_id);
+    }
 
-    caller
-        .balance
-        .checked_sub(1_000 * ONE_TOKEN)
-        .expect("Not enough balance"); // TODO: handle error
+    let params = account_management::account_creation_params();
+    let account = account_management::Account::try_create(req.account_id, req.address);
+    let creator = account_management::account(&creator_id).expect("Creator not found"); // TODO: handle error
 
-    account.balance = account.balance.checked_add(1_000 * ONE_TOKEN).unwrap();
+    account_management::pay_fee(creator, params.creation_fee)
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
 
-    update_account(account);
-    update_account(caller);
+    // Reloaded, the creator may be the fee collector
+    let creator = account_management::account(&creator_id).unwrap();
+    account_management::transfer(creator, account.evm_address.into(), params.initial_balance)
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
 
     system_env::commit(())
 }
 
+/// Sub-accounts are created by their parent, e.g. `sub.alice.multivm` by `alice.multivm`.
+/// Top-level names, i.e. children of the system meta-contract, are created by the registrar
+fn check_account_creator(account_id: &MultiVmAccountId, creator_id: &AccountId) {
+    let system_id = AccountId::system_meta_contract();
+    if AccountId::from(account_id.clone()) == system_id {
+        panic!("Account {} is reserved", account_id); // TODO: handle error
+    }
+
+    match account_id.parent().map(AccountId::from) {
+        Some(parent_id) if parent_id != system_id => {
+            if *creator_id != parent_id {
+                panic!("Only {} can create {}", parent_id, account_id); // TODO: handle error
+            }
+        }
+        _ => {
+            if account_management::registrar().as_ref() != Some(creator_id) {
+                panic!("Only the registrar can create {}", account_id); // TODO: handle error
+            }
+        }
+    }
+}
+
+/// Sets the account creating top-level names, called by the node at genesis only
+fn set_registrar(call: ContractCall) {
+    if system_env::signer() != AccountId::system_meta_contract() {
+        panic!("Genesis only method"); // TODO: handle error
+    }
+
+    let registrar_id: AccountId = call.try_deserialize_args().unwrap();
+    account_management::set_registrar(registrar_id);
+    system_env::commit(());
+}
+
+/// Sets the account receiving paid fees, called by the node at genesis only
+fn set_fee_collector(call: ContractCall) {
+    if system_env::signer() != AccountId::system_meta_contract() {
+        panic!("Genesis only method"); // TODO: handle error
+    }
+
+    let fee_collector_id: AccountId = call.try_deserialize_args().unwrap();
+    account_management::set_fee_collector(fee_collector_id);
+    system_env::commit(());
+}
+
+/// Sets the initial funding and the fee of account creation, called by the node at genesis only
+fn set_account_creation_params(call: ContractCall) {
+    if system_env::signer() != AccountId::system_meta_contract() {
+        panic!("Genesis only method"); // TODO: handle error
+    }
+
+    let params: AccountCreationParams = call.try_deserialize_args().unwrap();
+    account_management::set_account_creation_params(params);
+    system_env::commit(());
+}
+
+/// Applies the contract storage size change, called by the node on contract storage writes.
+/// Fails if the balance doesn't cover the storage
+fn update_storage_usage(call: ContractCall) {
+    // Contracts can't sign as the system, so they can't free their storage for nothing
+    if system_env::signer() != AccountId::system_meta_contract() {
+        panic!("Node only method"); // TODO: handle error
+    }
+
+    let update: StorageUsageUpdate = call.try_deserialize_args().unwrap();
+    let mut account = account_management::account(&update.account_id).expect("Account not found"); // TODO: handle error
+
+    // Storage written before the usage was tracked may be freed as well
+    account.storage_usage = account.storage_usage.saturating_add_signed(update.delta);
+    if update.delta > 0 && account.balance < account.locked_balance() {
+        panic!(
+            "Not enough balance for {} to cover {} bytes of storage (balance {}, required {})",
+            update.account_id,
+            account.storage_usage,
+            account.balance,
+            account.locked_balance()
+        ); // TODO: handle error
+    }
+
+    update_account(account);
+    system_env::commit(());
+}
+
+/// Validators are known by the MultiVM name if the account has one, so both aliases stake the same
+fn validator_id(account: &account_management::Account) -> AccountId {
+    match &account.multivm_account_id {
+        Some(multivm_account_id) => multivm_account_id.clone().into(),
+        None => account.evm_address.clone().into(),
+    }
+}
+
+/// Moves the caller tokens to the validator stake, staking again adds to it
+/// and replaces the key and the commission
+fn stake(call: ContractCall) {
+    let args: StakeArgs = call.try_deserialize_args().unwrap();
+    let mut staker = account_management::account(&system_env::caller()).expect("Staker not found"); // TODO: handle error
+
+    if args.amount == 0 {
+        panic!("Nothing to stake"); // TODO: handle error
+    }
+    if args.commission > MAX_COMMISSION {
+        panic!("Commission can't be over {} basis points", MAX_COMMISSION); // TODO: handle error
+    }
+    k256::ecdsa::VerifyingKey::from_sec1_bytes(&args.public_key)
+        .expect("Invalid validator public key"); // TODO: handle error
+
+    if staker.available_balance() < args.amount {
+        panic!(
+            "Not enough balance to stake (balance {}, required {})",
+            staker.available_balance(),
+            args.amount
+        ); // TODO: handle error
+    }
+    staker.balance -= args.amount;
+
+    staking::add_stake(
+        validator_id(&staker),
+        args.public_key,
+        args.commission,
+        args.amount,
+    );
+    update_account(staker);
+    system_env::commit(());
+}
+
+/// Removes tokens from the caller stake, they can be withdrawn after the unbonding period
+fn unstake(call: ContractCall) {
+    let amount: u128 = call.try_deserialize_args().unwrap();
+    let account_id = system_env::caller();
+    let account = account_management::account(&account_id).expect("Account not found"); // TODO: handle error
+
+    if amount == 0 {
+        panic!("Nothing to unstake"); // TODO: handle error
+    }
+    staking::remove_stake(&validator_id(&account), amount);
+
+    let mut unbonding = account_management::unbonding(&account_id);
+    unbonding.push(Unbonding {
+        amount,
+        unlock_height: system_env::block_height() + UNBONDING_PERIOD,
+    });
+    account_management::set_unbonding(&account_id, unbonding);
+    system_env::commit(());
+}
+
+/// Returns the caller tokens which finished unbonding to its balance
+fn withdraw() {
+    let account_id = system_env::caller();
+    let mut account = account_management::account(&account_id).expect("Account not found"); // TODO: handle error
+
+    let (unlocked, locked): (Vec<_>, Vec<_>) = account_management::unbonding(&account_id)
+        .into_iter()
+        .partition(|unbonding| unbonding.unlock_height <= system_env::block_height());
+    let amount: u128 = unlocked.iter().map(|unbonding| unbonding.amount).sum();
+    if amount == 0 {
+        panic!("Nothing to withdraw"); // TODO: handle error
+    }
+
+    account.balance = account
+        .balance
+        .checked_add(amount)
+        .expect("Balance overflow"); // TODO: handle error
+    update_account(account);
+    account_management::set_unbonding(&account_id, locked);
+    system_env::commit(());
+}
+
+/// Saves the validator set of the epoch, called by the node on the first block of each epoch
+fn snapshot_validators(call: ContractCall) {
+    if system_env::signer() != AccountId::system_meta_contract() {
+        panic!("Node only method"); // TODO: handle error
+    }
+
+    let epoch: u64 = call.try_deserialize_args().unwrap();
+    staking::snapshot(epoch);
+    system_env::commit(());
+}
+
+/// Returns the signer of a call made by its transaction directly. Contracts called by
+/// the transaction run with the same signer, so they can't manage the signer account
+fn direct_signer() -> AccountId {
+    let signer_id = system_env::signer();
+    if system_env::caller() != signer_id {
+        panic!("Only the signer can call it directly"); // TODO: handle error
+    }
+
+    signer_id
+}
+
+/// Registers a new access key of the signer account
+fn add_key(call: ContractCall) {
+    let key: AccessKey = call.try_deserialize_args().unwrap();
+    if let AccessKeyPermission::FunctionCall { contract_id, .. } = &key.permission {
+        // Otherwise a limited key could manage keys of the account
+        if *contract_id == AccountId::system_meta_contract() {
+            panic!("Function call key can't call system meta-contract"); // TODO: handle error
+        }
+    }
+
+    let signer_id = direct_signer();
+    let mut keys = account_management::access_keys(&signer_id);
+    if keys.iter().any(|existing| existing.address == key.address) {
+        panic!("Access key already exists"); // TODO: handle error
+    }
+
+    keys.push(key);
+    account_management::update_access_keys(&signer_id, keys);
+    system_env::commit(());
+}
+
+/// Removes access key of the signer account, the last full access key can't be removed
+fn remove_key(call: ContractCall) {
+    let address: EvmAddress = call.try_deserialize_args().unwrap();
+
+    let signer_id = direct_signer();
+    let mut keys = account_management::access_keys(&signer_id);
+    let count = keys.len();
+    keys.retain(|key| key.address != address);
+    if keys.len() == count {
+        panic!("Access key not found"); // TODO: handle error
+    }
+    if !keys
+        .iter()
+        .any(|key| key.permission == AccessKeyPermission::FullAccess)
+    {
+        panic!("Account must keep a full access key"); // TODO: handle error
+    }
+
+    account_management::update_access_keys(&signer_id, keys);
+    system_env::commit(());
+}
+
+/// Replaces access key of the signer account, keeping its permission
+fn rotate_key(call: ContractCall) {
+    let (old_address, new_address): (EvmAddress, EvmAddress) = call.try_deserialize_args().unwrap();
+
+    let signer_id = direct_signer();
+    let mut keys = account_management::access_keys(&signer_id);
+    if keys.iter().any(|key| key.address == new_address) {
+        panic!("Access key already exists"); // TODO: handle error
+    }
+
+    let key = keys
+        .iter_mut()
+        .find(|key| key.address == old_address)
+        .expect("Access key not found"); // TODO: handle error
+    key.address = new_address;
+
+    account_management::update_access_keys(&signer_id, keys);
+    system_env::commit(());
+}
+
+/// Makes the signer account multisig, or changes its keys and threshold
+fn set_multisig(call: ContractCall) {
+    let multisig: Multisig = call.try_deserialize_args().unwrap();
+
+    let mut keys = multisig.keys.clone();
+    keys.sort();
+    keys.dedup();
+    if keys.len() != multisig.keys.len() {
+        panic!("Duplicate multisig keys"); // TODO: handle error
+    }
+    if multisig.threshold == 0 || multisig.threshold as usize > keys.len() {
+        panic!("Invalid multisig threshold"); // TODO: handle error
+    }
+
+    account_management::update_multisig(&direct_signer(), multisig);
+    system_env::commit(());
+}
+
+/// Makes the signer account regular, its access keys are used again
+fn remove_multisig() {
+    let signer_id = direct_signer();
+    if account_management::multisig(&signer_id).is_none() {
+        panic!("Account is not multisig"); // TODO: handle error
+    }
+
+    account_management::remove_multisig(&signer_id);
+    system_env::commit(());
+}
+
+fn multisig(context: ContractCallContext) {
+    let account_id: AccountId = context.contract_call.try_deserialize_args().unwrap();
+    system_env::commit(account_management::multisig(&account_id))
+}
+
+fn access_keys(context: ContractCallContext) {
+    let account_id: AccountId = context.contract_call.try_deserialize_args().unwrap();
+    let keys = account_management::account_exists(&account_id)
+        .then(|| account_management::access_keys(&account_id));
+    system_env::commit(keys)
+}
+
 fn account_info(context: ContractCallContext) {
     let account_id: AccountId = context.contract_call.try_deserialize_args().unwrap();
     let account = account_management::account(&account_id);
@@ -303,16 +987,121 @@ fn transfer(context: ContractCallContext) {
     let (receiver, amount): (AccountId, u128) =
         context.contract_call.try_deserialize_args().unwrap();
     let sender = account_management::account(&context.sender_id).unwrap();
-    account_management::transfer(sender, receiver, amount);
+    account_management::transfer(sender, receiver, amount)
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
     system_env::commit(())
 }
 
+/// Deploys the image attached to the transaction to the signer account
 fn deploy_multivm_contract(call: ContractCall) {
     let req: ContractDeploymentArgs = call.try_deserialize_args().unwrap();
-    let mut account =
-        account_management::account(&system_env::signer()).expect("Account not found"); // TODO: handle error
 
-    system_env::deploy_contract(system_env::signer(), req.image_id);
+    code_store::register(req.image_id);
+    deploy_image(&system_env::signer(), req.image_id);
+    system_env::commit(());
+}
+
+/// Uploads the image attached to the transaction, so contracts can be deployed from it
+fn upload_code(call: ContractCall) {
+    let req: ContractDeploymentArgs = call.try_deserialize_args().unwrap();
+
+    code_store::register(req.image_id);
+    system_env::commit(());
+}
+
+/// Deploys already uploaded image to the signer account, without attaching it
+fn deploy_from_image(call: ContractCall) {
+    let req: ContractDeploymentArgs = call.try_deserialize_args().unwrap();
+
+    if code_store::get(&req.image_id).is_none() {
+        panic!("Code {} is not uploaded", image_id_hex(&req.image_id)); // TODO: handle error
+    }
+    deploy_image(&system_env::signer(), req.image_id);
+    system_env::commit(());
+}
+
+/// Creates sub-account of the calling contract and deploys the uploaded image to it,
+/// e.g. `pool1.amm.multivm` by `amm.multivm`
+fn create_sub_contract(call: ContractCall) {
+    let req: SubContractCreationRequest = call.try_deserialize_args().unwrap();
+    let factory = account_management::account(&system_env::caller()).expect("Caller not found"); // TODO: handle error
+
+    let (Some(Executable::MultiVm(_)), Some(factory_id)) =
+        (&factory.executable, &factory.multivm_account_id)
+    else {
+        panic!("Only MultiVM contracts can create sub-contracts"); // TODO: handle error
+    };
+    let AccountId::MultiVm(account_id) = &req.account_id else {
+        panic!("Sub-contract needs MultiVM account id"); // TODO: handle error
+    };
+    if account_id.parent().as_ref() != Some(factory_id) {
+        panic!("{} is not a sub-account of {}", account_id, factory_id); // TODO: handle error
+    }
+    if code_store::get(&req.image_id).is_none() {
+        panic!("Code {} is not uploaded", image_id_hex(&req.image_id)); // TODO: handle error
+    }
+
+    // Address of the contract is derived from its name, nobody knows its key. Anyone can send
+    // tokens to it beforehand, then the sub-contract takes over the EVM-only account
+    let hash = ethers_core::utils::keccak256(account_id.to_string().as_bytes());
+    let address: [u8; 20] = hash[12..].try_into().unwrap();
+    match account_management::account(&EvmAddress::from(address).into()) {
+        Some(account) => account_management::assign_multivm_alias(account, account_id.clone()),
+        None => {
+            account_management::Account::try_create(Some(account_id.clone()), address.into());
+        }
+    }
+    // The sub-contract is controlled by its code only
+    account_management::update_access_keys(&req.account_id, vec![]);
+    // Paid and funded by the factory, otherwise the balance can't cover any storage of the
+    // sub-contract
+    let params = account_management::account_creation_params();
+    account_management::pay_fee(factory, params.creation_fee)
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+
+    // Reloaded, the factory may be the fee collector
+    let factory = account_management::account(&system_env::caller()).unwrap();
+    account_management::transfer(factory, req.account_id.clone(), params.initial_balance)
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+
+    deploy_image(&req.account_id, req.image_id);
+    system_env::commit(());
+}
+
+fn deploy_image(account_id: &AccountId, image_id: [u32; 8]) {
+    let mut account = account_management::account(account_id).expect("Account not found"); // TODO: handle error
+
+    if account.executable.is_some() {
+        panic!("Contract is already deployed, use upgrade_contract"); // TODO: handle error
+    }
+
+    system_env::deploy_contract(account_id.clone(), image_id);
+    account.executable = Some(MultiVmExecutable { image_id }.into());
+    update_account(account);
+    account_management::push_code_version(account_id, image_id);
+    code_store::acquire(image_id);
+}
+
+/// Replaces code of the signer contract and runs the migration with the new code, if any.
+/// The new image is either attached to the transaction or uploaded before
+fn upgrade_multivm_contract(call: ContractCall) {
+    let req: ContractUpgradeArgs = call.try_deserialize_args().unwrap();
+    let signer_id = direct_signer();
+    let mut account = account_management::account(&signer_id).expect("Account not found"); // TODO: handle error
+
+    let Some(Executable::MultiVm(executable)) = &account.executable else {
+        panic!("MultiVM contract is not deployed"); // TODO: handle error
+    };
+    let previous_image_id = executable.image_id;
+    if previous_image_id == req.image_id {
+        panic!("Contract code is not changed"); // TODO: handle error
+    }
+    if account_management::is_immutable(&signer_id) {
+        panic!("Contract is immutable"); // TODO: handle error
+    }
+
+    code_store::register(req.image_id);
+    system_env::deploy_contract(signer_id.clone(), req.image_id);
     account.executable = Some(
         MultiVmExecutable {
             image_id: req.image_id,
@@ -320,37 +1109,364 @@ fn deploy_multivm_contract(call: ContractCall) {
         .into(),
     );
     update_account(account);
+    account_management::push_code_version(&signer_id, req.image_id);
+    code_store::acquire(req.image_id);
+
+    if let Some(migration) = req.migration {
+        if migration.deposit != 0 {
+            panic!("Migration can't attach deposit"); // TODO: handle error
+        }
+        charge_gas(migration.gas);
+
+        let commitment = system_env::cross_contract_call_raw(
+            signer_id,
+            migration.method,
+            migration.gas,
+            0,
+            migration.args,
+        );
+        if let Err(error) = commitment.response {
+            panic!("Migration failed: {:?}", error); // TODO: handle error
+        }
+    }
+
+    // Released after the migration, as the previous code may be removed
+    code_store::release(previous_image_id);
+
     system_env::commit(());
 }
 
-fn contract_call(contract_id: AccountId, call: ContractCall) {
-    let commitment =
-        system_env::cross_contract_call_raw(contract_id, call.method, call.gas, call.args);
+/// Forbids further upgrades of the signer contract, can't be undone
+fn make_immutable() {
+    let signer_id = direct_signer();
+    let account = account_management::account(&signer_id).expect("Account not found"); // TODO: handle error
+
+    let Some(Executable::MultiVm(_)) = account.executable else {
+        panic!("MultiVM contract is not deployed"); // TODO: handle error
+    };
+
+    account_management::make_immutable(&signer_id);
+    system_env::commit(());
+}
+
+fn code_history(context: ContractCallContext) {
+    let account_id: AccountId = context.contract_call.try_deserialize_args().unwrap();
+    let history = account_management::account_exists(&account_id)
+        .then(|| account_management::code_history(&account_id));
+    system_env::commit(history)
+}
+
+fn stored_code(context: ContractCallContext) {
+    let image_id: [u32; 8] = context.contract_call.try_deserialize_args().unwrap();
+    system_env::commit(code_store::get(&image_id))
+}
+
+fn total_supply(_context: ContractCallContext) {
+    system_env::commit(account_management::total_supply())
+}
+
+fn validators(_context: ContractCallContext) {
+    system_env::commit(staking::validators())
+}
+
+fn epoch_validators(context: ContractCallContext) {
+    let epoch: u64 = context.contract_call.try_deserialize_args().unwrap();
+    system_env::commit(staking::epoch_validators(epoch))
+}
+
+fn unbonding(context: ContractCallContext) {
+    let account_id: AccountId = context.contract_call.try_deserialize_args().unwrap();
+    let unbonding = account_management::account_exists(&account_id)
+        .then(|| account_management::unbonding(&account_id));
+    system_env::commit(unbonding)
+}
+
+fn is_immutable(context: ContractCallContext) {
+    let account_id: AccountId = context.contract_call.try_deserialize_args().unwrap();
+    system_env::commit(
+        account_management::account_exists(&account_id)
+            && account_management::is_immutable(&account_id),
+    )
+}
+
+/// Stores Solidity ABI of the signer contract, so it can be called with ABI encoded data
+fn declare_abi(call: ContractCall) {
+    let abi: Vec<String> = call.try_deserialize_args().unwrap();
+    solidity_abi::parse(&abi);
+
+    let signer_id = direct_signer();
+    let account = account_management::account(&signer_id).expect("Account not found"); // TODO: handle error
+    let Some(Executable::MultiVm(_)) = account.executable else {
+        panic!("ABI can be declared only by MultiVM contract"); // TODO: handle error
+    };
+
+    account_management::update_account_storage(&signer_id, solidity_abi::ABI_KEY.into(), abi);
+    system_env::commit(());
+}
+
+/// Charges the EVM sender for the MultiVM call made by its transaction, the intrinsic gas
+/// and the cycles used by the call are paid, up to the gas limit
+fn charge_multivm_call_gas(from: eth_primitive_types::H160, gas_limit: u64, gas_price: u128) {
+    let gas_used = TRANSFER_GAS
+        .saturating_add(system_env::last_call_cycles())
+        .min(gas_limit);
+    evm::charge_gas_fee(from, gas_used, gas_price);
+}
+
+/// Calls MultiVM contract with ABI encoded data, the output is ABI encoded as well.
+/// The deposit is returned to the depositor if the call fails
+fn abi_contract_call(
+    contract_id: AccountId,
+    depositor_id: AccountId,
+    abi: Vec<String>,
+    data: Vec<u8>,
+    gas: u64,
+    deposit: u128,
+) -> ContractResponse {
+    let abi = solidity_abi::parse(&abi);
+    let (function, args) = solidity_abi::decode_call(&abi, &data);
+
+    let commitment = system_env::cross_contract_call_raw(
+        contract_id.clone(),
+        function.name.clone(),
+        gas,
+        deposit,
+        args,
+    );
+    refund_failed_deposit(&commitment, contract_id, depositor_id, deposit);
+
+    commitment
+        .response
+        .map(|output| solidity_abi::encode_output(function, &output))
+}
+
+/// Charges gas attached to the call to the fee payer
+fn charge_gas(gas: u64) {
+    let fee_payer_id = system_env::fee_payer();
+    let fee_payer =
+        account_management::account(&fee_payer_id).expect("Fee payer account not found"); // TODO: handle error
+    if let Err(error) = account_management::pay_fee(fee_payer, gas as u128) {
+        panic!("Fee payer {}: {}", fee_payer_id, error); // TODO: handle error
+    }
+}
 
+fn contract_call(contract_id: AccountId, call: ContractCall) {
     let signer_id = system_env::signer();
-    // if signer == system_meta then its multivm call in evm wrapper probably
-    if signer_id != AccountId::system_meta_contract() {
-        let mut signer = account_management::account(&signer_id).expect("Signer account not found"); // TODO: handle error
-        signer.balance = signer
-            .balance
-            .checked_sub(call.gas as u128)
-            .expect(&format!(
-                "Not enough balance for {} (balance {}, required {})",
-                signer_id, signer.balance, call.gas
-            ));
+    // if signer == system_meta then its multivm call in evm wrapper probably,
+    // the wrapper moves the deposit by itself
+    let paid_deposit = signer_id != AccountId::system_meta_contract();
+    if paid_deposit {
+        charge_gas(call.gas);
+
+        // Deposit is attached by the signer, even if the transaction is relayed
+        let signer = account_management::account(&signer_id).expect("Signer account not found"); // TODO: handle error
+        account_management::transfer(signer, contract_id.clone(), call.deposit)
+            .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+    }
 
-        account_management::update_account(signer);
+    let commitment = system_env::cross_contract_call_raw(
+        contract_id.clone(),
+        call.method,
+        call.gas,
+        call.deposit,
+        call.args,
+    );
+    if paid_deposit {
+        refund_failed_deposit(&commitment, contract_id, signer_id, call.deposit);
     }
 
     system_env::commit(commitment);
 }
 
+/// Returns the deposit of a failed call, the contract changes are discarded but the transfer isn't
+fn refund_failed_deposit(
+    commitment: &Commitment,
+    contract_id: AccountId,
+    depositor_id: AccountId,
+    deposit: u128,
+) {
+    if commitment.response.is_ok() || deposit == 0 {
+        return;
+    }
+
+    let contract = account_management::account(&contract_id).expect("Contract not found"); // TODO: handle error
+    account_management::transfer(contract, depositor_id, deposit)
+        .unwrap_or_else(|error| panic!("{}", error)); // TODO: handle error
+}
+
+/// Content-addressed store of contract images, contracts deployed from the same image share it
+mod code_store {
+    use multivm_primitives::{image_id_hex, StoredCode};
+
+    use crate::system_env;
+
+    fn key(image_id: &[u32; 8]) -> String {
+        format!("code_store.{}", image_id_hex(image_id))
+    }
+
+    pub fn get(image_id: &[u32; 8]) -> Option<StoredCode> {
+        system_env::get_storage(key(image_id))
+    }
+
+    /// Uploads the image attached to the transaction, unless it is already stored
+    pub fn register(image_id: [u32; 8]) {
+        if get(&image_id).is_some() {
+            return;
+        }
+
+        system_env::upload_code(image_id);
+        let code = StoredCode {
+            uploader: system_env::signer(),
+            refs: 0,
+        };
+        system_env::set_storage(key(&image_id), code);
+    }
+
+    /// Counts a contract deployed from the image
+    pub fn acquire(image_id: [u32; 8]) {
+        let mut code = get(&image_id).expect("Code is not uploaded"); // TODO: handle error
+        code.refs += 1;
+        system_env::set_storage(key(&image_id), code);
+    }
+
+    /// Uncounts a contract no longer running the image, the last one removes the image
+    pub fn release(image_id: [u32; 8]) {
+        // Contracts deployed before the code store are not counted
+        let Some(mut code) = get(&image_id) else {
+            return;
+        };
+
+        code.refs = code.refs.saturating_sub(1);
+        if code.refs == 0 {
+            system_env::delete_storage(key(&image_id));
+            system_env::delete_code(image_id);
+        } else {
+            system_env::set_storage(key(&image_id), code);
+        }
+    }
+}
+
+mod staking {
+    use multivm_primitives::{AccountId, Validator};
+
+    use crate::system_env;
+
+    /// System storage key of the current validator set
+    const VALIDATORS_KEY: &str = "validators";
+
+    fn snapshot_key(epoch: u64) -> String {
+        format!("validators_snapshot.{}", epoch)
+    }
+
+    pub fn validators() -> Vec<Validator> {
+        system_env::get_storage(VALIDATORS_KEY.into()).unwrap_or_default()
+    }
+
+    /// Adds to the validator stake, the account joins the set on its first stake
+    pub fn add_stake(account_id: AccountId, public_key: Vec<u8>, commission: u16, amount: u128) {
+        let mut validators = validators();
+
+        match validators
+            .iter_mut()
+            .find(|validator| validator.account_id == account_id)
+        {
+            Some(validator) => {
+                validator.stake = validator.stake.checked_add(amount).expect("Stake overflow"); // TODO: handle error
+                validator.public_key = public_key;
+                validator.commission = commission;
+            }
+            None => validators.push(Validator {
+                account_id,
+                public_key,
+                stake: amount,
+                commission,
+            }),
+        }
+
+        system_env::set_storage(VALIDATORS_KEY.into(), validators);
+    }
+
+    /// Removes from the validator stake, the account leaves the set once nothing is staked
+    pub fn remove_stake(account_id: &AccountId, amount: u128) {
+        let mut validators = validators();
+
+        let index = validators
+            .iter()
+            .position(|validator| &validator.account_id == account_id)
+            .expect("Validator not found"); // TODO: handle error
+        let validator = &mut validators[index];
+        validator.stake = validator.stake.checked_sub(amount).expect(&format!(
+            "Not enough stake (stake {}, required {})",
+            validator.stake, amount
+        )); // TODO: handle error
+        if validator.stake == 0 {
+            validators.remove(index);
+        }
+
+        system_env::set_storage(VALIDATORS_KEY.into(), validators);
+    }
+
+    /// Returns the validator set as it was at the start of the epoch
+    pub fn epoch_validators(epoch: u64) -> Option<Vec<Validator>> {
+        system_env::get_storage(snapshot_key(epoch))
+    }
+
+    pub fn snapshot(epoch: u64) {
+        system_env::set_storage(snapshot_key(epoch), validators());
+    }
+}
+
 mod account_management {
     use borsh::{BorshDeserialize, BorshSerialize};
-    use multivm_primitives::{AccountId, EvmAddress, MultiVmAccountId};
+    use multivm_primitives::{
+        AccessKey, AccountCreationParams, AccountId, CodeVersion, EvmAddress, MultiVmAccountId,
+        Multisig, Unbonding, STORAGE_PRICE_PER_BYTE,
+    };
 
     use crate::system_env;
 
+    /// System storage key of the account creating top-level names
+    const REGISTRAR_KEY: &str = "registrar";
+    /// System storage key of the account creation cost
+    const ACCOUNT_CREATION_PARAMS_KEY: &str = "account_creation_params";
+    /// System storage key of the account receiving paid fees
+    const FEE_COLLECTOR_KEY: &str = "fee_collector";
+    /// System storage key of the native token supply
+    const TOTAL_SUPPLY_KEY: &str = "total_supply";
+    /// Account storage key of the access keys list
+    const ACCESS_KEYS_KEY: &str = "access_keys";
+    /// Account storage key of the multisig keys and threshold
+    const MULTISIG_KEY: &str = "multisig";
+    /// Account storage key of the contract code versions
+    const CODE_HISTORY_KEY: &str = "code_history";
+    /// Account storage key of the flag forbidding contract upgrades
+    const IMMUTABLE_KEY: &str = "immutable";
+    /// Account storage key of the unstaked tokens waiting for withdrawal
+    const UNBONDING_KEY: &str = "unbonding";
+
+    #[derive(Debug)]
+    pub enum TransferError {
+        NotEnoughBalance { balance: u128, required: u128 },
+        ReceiverNotFound(AccountId),
+        BalanceOverflow,
+    }
+
+    impl std::fmt::Display for TransferError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                TransferError::NotEnoughBalance { balance, required } => write!(
+                    f,
+                    "Not enough balance (balance {}, required {})",
+                    balance, required
+                ),
+                TransferError::ReceiverNotFound(receiver_id) => {
+                    write!(f, "Receiver {} not found", receiver_id)
+                }
+                TransferError::BalanceOverflow => write!(f, "Receiver balance overflow"),
+            }
+        }
+    }
+
     #[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
     pub enum Executable {
         Evm(),
@@ -376,6 +1492,8 @@ mod account_management {
         pub executable: Option<Executable>,
         pub balance: u128,
         pub nonce: u64,
+        /// Bytes of contract storage, lock `STORAGE_PRICE_PER_BYTE` of the balance each
+        pub storage_usage: u64,
     }
 
     impl Account {
@@ -401,11 +1519,21 @@ mod account_management {
                 executable: None,
                 balance: 0,
                 nonce: 0,
+                storage_usage: 0,
             };
 
             register_account(account.clone());
             account
         }
+
+        pub fn locked_balance(&self) -> u128 {
+            self.storage_usage as u128 * STORAGE_PRICE_PER_BYTE
+        }
+
+        /// Balance not locked for storage, the one transfers and fees can spend
+        pub fn available_balance(&self) -> u128 {
+            self.balance.saturating_sub(self.locked_balance())
+        }
     }
 
     /// Returns account by alias
@@ -473,6 +1601,23 @@ mod account_management {
         }
     }
 
+    /// Gives the name to the EVM-only account, its balance and nonce are kept
+    pub fn assign_multivm_alias(mut account: Account, multivm_account_id: MultiVmAccountId) {
+        if account.multivm_account_id.is_some() || account.executable.is_some() {
+            panic!("Account alias already exists"); // TODO: handle error
+        }
+        if account_exists(&multivm_account_id.clone().into()) {
+            panic!("Account alias already exists"); // TODO: handle error
+        }
+
+        system_env::set_storage(
+            format!("accounts_aliases.multivm.{}", multivm_account_id),
+            account.internal_id,
+        );
+        account.multivm_account_id = Some(multivm_account_id);
+        update_account(account);
+    }
+
     fn account_internal_id_by_multivm_alias(multivm_account_id: MultiVmAccountId) -> Option<u128> {
         system_env::get_storage(format!("accounts_aliases.multivm.{}", multivm_account_id))
     }
@@ -490,13 +1635,153 @@ mod account_management {
         system_env::set_storage(format!("accounts.{}", account.internal_id), account);
     }
 
-    pub fn transfer(mut sender: Account, receiver_id: AccountId, amount: u128) {
+    /// Removes account and its alias mappings from the system,
+    /// account storage has to be removed separately with `delete_account_storage`
+    pub fn delete_account(account_id: &AccountId) {
+        let account = account(account_id).expect("Account not found"); // TODO: handle error
+
+        system_env::delete_storage(format!("accounts.{}", account.internal_id));
+
+        if let Some(multivm_account_id) = account.multivm_account_id {
+            system_env::delete_storage(format!("accounts_aliases.multivm.{}", multivm_account_id));
+        }
+
+        let address = account.evm_address.to_string().to_lowercase();
+        system_env::delete_storage(format!("accounts_aliases.evm.{}", address));
+    }
+
+    /// Returns keys allowed to sign account transactions,
+    /// the key of the EVM alias has full access until the keys are changed
+    pub fn access_keys(account_id: &AccountId) -> Vec<AccessKey> {
+        let account = account(account_id).expect("Account not found"); // TODO: handle error
+        account_storage(account_id, ACCESS_KEYS_KEY.into())
+            .unwrap_or_else(|| vec![AccessKey::full_access(account.evm_address)])
+    }
+
+    pub fn update_access_keys(account_id: &AccountId, keys: Vec<AccessKey>) {
+        update_account_storage(account_id, ACCESS_KEYS_KEY.into(), keys);
+    }
+
+    /// Returns multisig configuration, `None` for regular accounts
+    pub fn multisig(account_id: &AccountId) -> Option<Multisig> {
+        account_storage(account_id, MULTISIG_KEY.into())
+    }
+
+    pub fn update_multisig(account_id: &AccountId, multisig: Multisig) {
+        update_account_storage(account_id, MULTISIG_KEY.into(), multisig);
+    }
+
+    pub fn remove_multisig(account_id: &AccountId) {
+        delete_account_storage(account_id, MULTISIG_KEY.into());
+    }
+
+    pub fn registrar() -> Option<AccountId> {
+        system_env::get_storage(REGISTRAR_KEY.into())
+    }
+
+    pub fn set_registrar(registrar_id: AccountId) {
+        system_env::set_storage(REGISTRAR_KEY.into(), registrar_id);
+    }
+
+    pub fn fee_collector() -> Option<AccountId> {
+        system_env::get_storage(FEE_COLLECTOR_KEY.into())
+    }
+
+    pub fn set_fee_collector(fee_collector_id: AccountId) {
+        system_env::set_storage(FEE_COLLECTOR_KEY.into(), fee_collector_id);
+    }
+
+    pub fn account_creation_params() -> AccountCreationParams {
+        system_env::get_storage(ACCOUNT_CREATION_PARAMS_KEY.into()).unwrap_or_default()
+    }
+
+    pub fn set_account_creation_params(params: AccountCreationParams) {
+        system_env::set_storage(ACCOUNT_CREATION_PARAMS_KEY.into(), params);
+    }
+
+    pub fn code_history(account_id: &AccountId) -> Vec<CodeVersion> {
+        account_storage(account_id, CODE_HISTORY_KEY.into()).unwrap_or_default()
+    }
+
+    /// Records code deployed to the contract in the current block
+    pub fn push_code_version(account_id: &AccountId, image_id: [u32; 8]) {
+        let mut history = code_history(account_id);
+        history.push(CodeVersion {
+            image_id,
+            block_height: system_env::block_height(),
+        });
+        update_account_storage(account_id, CODE_HISTORY_KEY.into(), history);
+    }
+
+    pub fn unbonding(account_id: &AccountId) -> Vec<Unbonding> {
+        account_storage(account_id, UNBONDING_KEY.into()).unwrap_or_default()
+    }
+
+    pub fn set_unbonding(account_id: &AccountId, unbonding: Vec<Unbonding>) {
+        update_account_storage(account_id, UNBONDING_KEY.into(), unbonding);
+    }
+
+    pub fn is_immutable(account_id: &AccountId) -> bool {
+        account_storage(account_id, IMMUTABLE_KEY.into()).unwrap_or_default()
+    }
+
+    pub fn make_immutable(account_id: &AccountId) {
+        update_account_storage(account_id, IMMUTABLE_KEY.into(), true);
+    }
+
+    /// Moves tokens between accounts, the sender may carry unsaved changes, e.g. bumped nonce
+    pub fn transfer(
+        mut sender: Account,
+        receiver_id: AccountId,
+        amount: u128,
+    ) -> Result<(), TransferError> {
+        if sender.available_balance() < amount {
+            return Err(TransferError::NotEnoughBalance {
+                balance: sender.available_balance(),
+                required: amount,
+            });
+        }
+
+        let mut receiver =
+            account(&receiver_id).ok_or(TransferError::ReceiverNotFound(receiver_id))?;
+        // Receiver may be the sender by another alias, its loaded copy is stale
+        if receiver.internal_id == sender.internal_id {
+            update_account(sender);
+            return Ok(());
+        }
+
+        receiver.balance = receiver
+            .balance
+            .checked_add(amount)
+            .ok_or(TransferError::BalanceOverflow)?;
         sender.balance -= amount;
-        update_account(sender);
 
-        let mut receiver = account(&receiver_id).expect("Receiver not found");
-        receiver.balance += amount;
+        update_account(sender);
         update_account(receiver);
+        Ok(())
+    }
+
+    /// Sum of all account balances, stakes and unbonding tokens, changed by minting only
+    pub fn total_supply() -> u128 {
+        system_env::get_storage(TOTAL_SUPPLY_KEY.into()).unwrap_or_default()
+    }
+
+    /// Credits new tokens to the account, the account has to be saved by the caller
+    pub fn mint(account: &mut Account, amount: u128) {
+        account.balance = account
+            .balance
+            .checked_add(amount)
+            .expect("Balance overflow"); // TODO: handle error
+        let total_supply = total_supply()
+            .checked_add(amount)
+            .expect("Total supply overflow"); // TODO: handle error
+        system_env::set_storage(TOTAL_SUPPLY_KEY.into(), total_supply);
+    }
+
+    /// Moves paid fees to the fee collector, so they stay in the supply
+    pub fn pay_fee(payer: Account, amount: u128) -> Result<(), TransferError> {
+        let fee_collector_id = fee_collector().expect("Fee collector is not set"); // TODO: handle error
+        transfer(payer, fee_collector_id, amount)
     }
 
     pub fn account_storage<T: BorshDeserialize>(account_id: &AccountId, key: String) -> Option<T> {
@@ -512,4 +1797,9 @@ mod account_management {
         let account = account(account_id).expect("Account not found"); // TODO: handle error
         system_env::set_storage(format!("accounts.{}.{}", account.internal_id, key), value);
     }
+
+    pub fn delete_account_storage(account_id: &AccountId, key: String) {
+        let account = account(account_id).expect("Account not found"); // TODO: handle error
+        system_env::delete_storage(format!("accounts.{}.{}", account.internal_id, key));
+    }
 }
@@ -5,11 +5,13 @@ use std::{collections::HashMap, sync::Mutex};
 
 use multivm_primitives::{
     syscalls::{
-        CrossContractCallRequest, DeployContractRequest, GetStorageResponse, SetStorageRequest,
-        CROSS_CONTRACT_CALL, DEPLOY_CONTRACT_CALL, GET_STORAGE_CALL, SET_STORAGE_CALL,
+        CrossContractCallRequest, DeleteCodeRequest, DeleteStorageRequest, DeployContractRequest,
+        GetStorageResponse, SetStorageRequest, UploadCodeRequest, CALL_CYCLES_CALL,
+        CROSS_CONTRACT_CALL, DELETE_CODE_CALL, DELETE_STORAGE_CALL, DEPLOY_CONTRACT_CALL,
+        EVM_TRACE_CALL, GET_STORAGE_CALL, SET_STORAGE_CALL, UPLOAD_CODE_CALL,
     },
     AccountId, Commitment, ContractCall, ContractCallContext, ContractError, Digest as HashDigest,
-    StorageKey,
+    EvmLog, EvmTrace, StorageKey,
 };
 
 pub fn setup_env(call: &ContractCallContext) {
@@ -22,14 +24,19 @@ pub fn setup_env(call: &ContractCallContext) {
 #[derive(Debug, BorshSerialize)]
 struct Env {
     signer_id: AccountId,
+    /// Account charged for gas, the signer unless the transaction is relayed
+    fee_payer_id: AccountId,
     caller_id: AccountId,
     contract_id: AccountId,
     gas: u64,
+    block_height: u64,
 
     call_hash: HashDigest,
     initial_storage_hashes: HashMap<StorageKey, HashDigest>,
-    storage_cache: HashMap<StorageKey, (Vec<u8>, bool)>,
+    /// Cached storage with change flags, `None` marks a deleted key
+    storage_cache: HashMap<StorageKey, (Option<Vec<u8>>, bool)>,
     cross_calls_hashes: Vec<(HashDigest, HashDigest)>,
+    logs: Vec<EvmLog>,
 }
 
 impl Env {
@@ -43,13 +50,16 @@ impl Env {
 
         Self {
             signer_id: call.signer_id.clone(),
+            fee_payer_id: call.signer_id.clone(),
             caller_id: call.sender_id.clone(),
             contract_id: call.contract_id.clone(),
             gas: call.contract_call.gas,
+            block_height: call.environment.block_height,
             call_hash: call_hash,
             initial_storage_hashes: Default::default(),
             storage_cache: Default::default(),
             cross_calls_hashes: Default::default(),
+            logs: Default::default(),
         }
     }
 }
@@ -62,6 +72,11 @@ impl Env {
         self.signer_id.clone()
     }
 
+    /// Returns the account charged for gas
+    pub fn fee_payer(&self) -> AccountId {
+        self.fee_payer_id.clone()
+    }
+
     /// Returns the current call sender
     pub fn caller(&self) -> AccountId {
         self.caller_id.clone()
@@ -72,6 +87,11 @@ impl Env {
         self.contract_id.clone()
     }
 
+    /// Returns height of the block the call is executed in
+    pub fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
     /// Makes a cross-contract call
     pub fn cross_contract_call(&mut self, req: CrossContractCallRequest) -> Commitment {
         let req_bytes = borsh::to_vec(&req).expect("Expected to serialize");
@@ -100,17 +120,20 @@ impl Env {
             .unwrap();
 
         self.cross_calls_hashes.push((req_hash, output_hash));
+        // Logged in execution order, after the logs emitted before the call
+        if commitment.response.is_ok() {
+            self.logs.extend(commitment.logs.iter().cloned());
+        }
 
         commitment
     }
 
     /// Returns the storage value for the given key, return None if storage is not exist
     pub fn get_storage<T: BorshDeserialize>(&mut self, key: StorageKey) -> Option<T> {
-        if let Some(storage_bytes) = self.storage_cache.get(&key) {
-            return Some(
-                BorshDeserialize::try_from_slice(storage_bytes.0.as_slice())
-                    .expect("Expected to deserialize"),
-            );
+        if let Some((storage_bytes, _)) = self.storage_cache.get(&key) {
+            return storage_bytes.as_ref().map(|bytes| {
+                BorshDeserialize::try_from_slice(bytes.as_slice()).expect("Expected to deserialize")
+            });
         }
 
         let response: Vec<u8> =
@@ -132,7 +155,7 @@ impl Env {
             .unwrap();
 
         self.storage_cache
-            .insert(key.clone(), (storage.clone(), false));
+            .insert(key.clone(), (Some(storage.clone()), false));
         self.initial_storage_hashes.insert(key, hash);
 
         Some(
@@ -145,7 +168,11 @@ impl Env {
         let storage_bytes = borsh::to_vec(&data).expect("Expected to serialize");
 
         self.storage_cache
-            .insert(key.clone(), (storage_bytes.clone(), true));
+            .insert(key.clone(), (Some(storage_bytes.clone()), true));
+    }
+
+    pub fn delete_storage(&mut self, key: String) {
+        self.storage_cache.insert(key, (None, true));
     }
 
     fn send_storage_update(key: String, storage: Vec<u8>) -> HashDigest {
@@ -169,6 +196,19 @@ impl Env {
         hash
     }
 
+    fn send_storage_deletion(key: String) {
+        let request = DeleteStorageRequest { key };
+
+        let to_host = borsh::to_vec(&request).expect("Expected to serialize");
+
+        let _: Vec<u8> =
+            risc0_zkvm::guest::env::send_recv_slice(DELETE_STORAGE_CALL, &to_host).to_vec();
+    }
+
+    pub fn emit_logs(&mut self, logs: Vec<EvmLog>) {
+        self.logs.extend(logs);
+    }
+
     pub fn deploy_contract(&self, account_id: AccountId, image_id: [u32; 8]) {
         let request = DeployContractRequest {
             account_id,
@@ -181,16 +221,37 @@ impl Env {
             risc0_zkvm::guest::env::send_recv_slice(DEPLOY_CONTRACT_CALL, &to_host).to_vec();
     }
 
+    pub fn upload_code(&self, image_id: [u32; 8]) {
+        let request = UploadCodeRequest { image_id };
+
+        let to_host = borsh::to_vec(&request).expect("Expected to serialize");
+
+        let _: Vec<u8> =
+            risc0_zkvm::guest::env::send_recv_slice(UPLOAD_CODE_CALL, &to_host).to_vec();
+    }
+
+    pub fn delete_code(&self, image_id: [u32; 8]) {
+        let request = DeleteCodeRequest { image_id };
+
+        let to_host = borsh::to_vec(&request).expect("Expected to serialize");
+
+        let _: Vec<u8> =
+            risc0_zkvm::guest::env::send_recv_slice(DELETE_CODE_CALL, &to_host).to_vec();
+    }
+
     pub fn commit<T: borsh::BorshSerialize>(self, output: T) {
         let Env {
             signer_id: _,
+            fee_payer_id: _,
             caller_id: _,
             contract_id: _,
             gas: _,
+            block_height: _,
             call_hash,
             initial_storage_hashes: _, // TODO: fix  storage
             storage_cache,
             cross_calls_hashes,
+            logs,
         } = self;
 
         let response = borsh::to_vec(&output).expect("Expected to serialize");
@@ -199,7 +260,12 @@ impl Env {
             .into_iter()
             .for_each(|(key, (storage, was_changed))| {
                 if was_changed {
-                    Env::send_storage_update(key.clone(), storage.clone());
+                    match storage {
+                        Some(storage) => {
+                            Env::send_storage_update(key.clone(), storage);
+                        }
+                        None => Env::send_storage_deletion(key.clone()),
+                    }
                 }
             });
 
@@ -209,6 +275,7 @@ impl Env {
             cross_calls_hashes: cross_calls_hashes,
             previous_account_root: Default::default(),
             new_account_root: Default::default(),
+            logs,
         };
 
         risc0_zkvm::guest::env::commit_slice(
@@ -221,13 +288,16 @@ impl Env {
     pub fn abort(self, message: String) {
         let Env {
             signer_id: _,
+            fee_payer_id: _,
             caller_id: _,
             contract_id: _,
             gas: _,
+            block_height: _,
             call_hash,
             initial_storage_hashes: _, // TODO: fix  storage
             storage_cache: _,
             cross_calls_hashes,
+            logs: _,
         } = self;
 
         let commitment = Commitment {
@@ -236,6 +306,7 @@ impl Env {
             cross_calls_hashes: cross_calls_hashes,
             previous_account_root: Default::default(),
             new_account_root: Default::default(),
+            logs: Default::default(),
         };
 
         risc0_zkvm::guest::env::commit_slice(
@@ -251,6 +322,16 @@ pub fn signer() -> AccountId {
     ENV.lock().unwrap().as_ref().unwrap().signer()
 }
 
+/// Returns the account charged for gas
+pub fn fee_payer() -> AccountId {
+    ENV.lock().unwrap().as_ref().unwrap().fee_payer()
+}
+
+/// Charges gas of the current transaction to another account, e.g. a relayer
+pub fn set_fee_payer(account_id: AccountId) {
+    ENV.lock().unwrap().as_mut().unwrap().fee_payer_id = account_id;
+}
+
 /// Returns the current call sender
 pub fn caller() -> AccountId {
     ENV.lock().unwrap().as_ref().unwrap().caller()
@@ -261,14 +342,20 @@ pub fn contract() -> AccountId {
     ENV.lock().unwrap().as_ref().unwrap().contract()
 }
 
+/// Returns height of the block the call is executed in
+pub fn block_height() -> u64 {
+    ENV.lock().unwrap().as_ref().unwrap().block_height()
+}
+
 /// Makes a cross-contract call with raw input
 pub fn cross_contract_call_raw(
     contract_id: AccountId,
     method: String,
     gas: u64,
+    deposit: u128,
     args: Vec<u8>,
 ) -> Commitment {
-    let call = ContractCall::new_raw(method, args, gas, 0);
+    let call = ContractCall::new_raw(method, args, gas, deposit);
     let req = CrossContractCallRequest::new(contract_id, call);
     ENV.lock()
         .unwrap()
@@ -286,6 +373,10 @@ pub fn set_storage<T: borsh::BorshSerialize>(key: String, data: T) {
     ENV.lock().unwrap().as_mut().unwrap().set_storage(key, data)
 }
 
+pub fn delete_storage(key: String) {
+    ENV.lock().unwrap().as_mut().unwrap().delete_storage(key)
+}
+
 pub fn commit<T: borsh::BorshSerialize>(output: T) {
     ENV.lock().unwrap().take().unwrap().commit(output)
 }
@@ -294,6 +385,27 @@ pub fn abort(message: String) {
     ENV.lock().unwrap().take().unwrap().abort(message)
 }
 
+/// Sends EVM execution trace to the host, happens in tracing mode only
+pub fn emit_trace(trace: EvmTrace) {
+    let to_host = borsh::to_vec(&trace).expect("Expected to serialize");
+
+    let _: Vec<u8> = risc0_zkvm::guest::env::send_recv_slice(EVM_TRACE_CALL, &to_host).to_vec();
+}
+
+/// Returns zkVM cycles used by the last cross-contract call, its cross-calls included.
+/// Answered by the transaction executor only
+pub fn last_call_cycles() -> u64 {
+    let response: Vec<u8> =
+        risc0_zkvm::guest::env::send_recv_slice::<u8, u8>(CALL_CYCLES_CALL, &[]).to_vec();
+
+    borsh::from_slice(&response).expect("Call cycles are corrupted")
+}
+
+/// Attaches EVM logs to the current call commitment
+pub fn emit_logs(logs: Vec<EvmLog>) {
+    ENV.lock().unwrap().as_mut().unwrap().emit_logs(logs)
+}
+
 pub fn deploy_contract(account_id: AccountId, image_id: [u32; 8]) {
     ENV.lock()
         .unwrap()
@@ -301,3 +413,13 @@ pub fn deploy_contract(account_id: AccountId, image_id: [u32; 8]) {
         .unwrap()
         .deploy_contract(account_id, image_id)
 }
+
+/// Stores the image attached to the transaction in the code store
+pub fn upload_code(image_id: [u32; 8]) {
+    ENV.lock().unwrap().as_ref().unwrap().upload_code(image_id)
+}
+
+/// Removes the image from the code store
+pub fn delete_code(image_id: [u32; 8]) {
+    ENV.lock().unwrap().as_ref().unwrap().delete_code(image_id)
+}
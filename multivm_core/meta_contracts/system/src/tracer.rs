@@ -0,0 +1,337 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use eth_primitive_types::{H160, U256};
+use evm::{CreateScheme, ExitReason};
+use multivm_primitives::{EvmCallFrame, EvmStructLog, EvmTrace};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables tracing of EVM executions, used in non-proving debug mode only
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Runs EVM execution with call and opcode level tracing, if tracing is enabled
+pub fn trace<T>(execute: impl FnOnce() -> T) -> (T, Option<Tracer>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return (execute(), None);
+    }
+
+    let tracer = Rc::new(RefCell::new(Tracer::default()));
+
+    let result = evm::tracing::using(&mut CallListener(tracer.clone()), || {
+        evm_runtime::tracing::using(&mut StepListener(tracer.clone()), || {
+            evm_gasometer::tracing::using(&mut GasListener(tracer.clone()), execute)
+        })
+    });
+
+    let tracer = tracer.replace(Tracer::default());
+    (result, Some(tracer))
+}
+
+#[derive(Default)]
+pub struct Tracer {
+    /// Frames of the calls in progress, the first one is the transaction call
+    frames: Vec<EvmCallFrame>,
+    root: Option<EvmCallFrame>,
+    struct_logs: Vec<EvmStructLog>,
+    /// Gas left in the current frame, as of the latest gasometer snapshot
+    gas_left: u64,
+    /// Gas used by the current frame, as of the latest gasometer snapshot
+    gas_used: u64,
+    step_priced: bool,
+}
+
+impl Tracer {
+    /// Builds the trace of the finished execution
+    pub fn finish(mut self, gas_used: u64, reason: &ExitReason, output: &[u8]) -> EvmTrace {
+        // Unwind frames left without exit event
+        while !self.frames.is_empty() {
+            self.exit(reason, output);
+        }
+
+        let mut call = self.root.unwrap_or_default();
+        call.gas_used = gas_used;
+
+        EvmTrace {
+            call,
+            struct_logs: self.struct_logs,
+            gas_used,
+            failed: !reason.is_succeed(),
+            return_value: output.to_vec(),
+        }
+    }
+
+    fn enter(&mut self, frame: EvmCallFrame) {
+        self.frames.push(frame);
+    }
+
+    fn exit(&mut self, reason: &ExitReason, output: &[u8]) {
+        // Root frame may be exited twice, by the call itself and by the transaction
+        let Some(mut frame) = self.frames.pop() else {
+            return;
+        };
+        frame.gas_used = self.gas_used;
+        frame.output = output.to_vec();
+        frame.error = match reason {
+            ExitReason::Succeed(_) => None,
+            ExitReason::Revert(_) => Some("execution reverted".to_string()),
+            ExitReason::Error(error) => Some(format!("{:?}", error)),
+            ExitReason::Fatal(error) => Some(format!("{:?}", error)),
+        };
+
+        match self.frames.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+fn frame(call_type: &str, from: H160, to: H160, value: U256, gas: Option<u64>) -> EvmCallFrame {
+    let mut value_bytes = [0u8; 32];
+    value.to_big_endian(&mut value_bytes);
+
+    EvmCallFrame {
+        call_type: call_type.to_string(),
+        from: from.0,
+        to: to.0,
+        value: value_bytes,
+        gas: gas.unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+struct CallListener(Rc<RefCell<Tracer>>);
+
+impl evm::tracing::EventListener for CallListener {
+    fn event(&mut self, event: evm::tracing::Event) {
+        use evm::tracing::Event;
+
+        let mut tracer = self.0.borrow_mut();
+        match event {
+            Event::Call {
+                code_address,
+                input,
+                target_gas,
+                is_static,
+                context,
+                ..
+            } => {
+                let call_type = if is_static {
+                    "STATICCALL"
+                } else if code_address != context.address {
+                    "DELEGATECALL"
+                } else {
+                    "CALL"
+                };
+                let mut frame = frame(
+                    call_type,
+                    context.caller,
+                    context.address,
+                    context.apparent_value,
+                    target_gas,
+                );
+                frame.input = input.to_vec();
+                tracer.enter(frame);
+            }
+            Event::Create {
+                caller,
+                address,
+                scheme,
+                value,
+                init_code,
+                target_gas,
+                ..
+            } => {
+                let call_type = match scheme {
+                    CreateScheme::Create2 { .. } => "CREATE2",
+                    _ => "CREATE",
+                };
+                let mut frame = frame(call_type, caller, address, value, target_gas);
+                frame.input = init_code.to_vec();
+                tracer.enter(frame);
+            }
+            Event::Suicide {
+                address,
+                target,
+                balance,
+                ..
+            } => {
+                let frame = frame("SELFDESTRUCT", address, target, balance, None);
+                if let Some(parent) = tracer.frames.last_mut() {
+                    parent.calls.push(frame);
+                }
+            }
+            Event::Exit {
+                reason,
+                return_value,
+            } => tracer.exit(reason, return_value),
+            _ => {}
+        }
+    }
+}
+
+struct StepListener(Rc<RefCell<Tracer>>);
+
+impl evm_runtime::tracing::EventListener for StepListener {
+    fn event(&mut self, event: evm_runtime::tracing::Event) {
+        let evm_runtime::tracing::Event::Step {
+            opcode,
+            position,
+            stack,
+            ..
+        } = event
+        else {
+            return;
+        };
+
+        let mut tracer = self.0.borrow_mut();
+        let log = EvmStructLog {
+            pc: position.as_ref().copied().unwrap_or_default() as u64,
+            op: opcode_name(opcode.0),
+            gas: tracer.gas_left,
+            gas_cost: 0,
+            depth: tracer.frames.len() as u64,
+            stack: stack.data().iter().map(|word| word.0).collect(),
+        };
+        tracer.struct_logs.push(log);
+        tracer.step_priced = false;
+    }
+}
+
+struct GasListener(Rc<RefCell<Tracer>>);
+
+impl evm_gasometer::tracing::EventListener for GasListener {
+    fn event(&mut self, event: evm_gasometer::tracing::Event) {
+        use evm_gasometer::tracing::Event;
+
+        let (cost, snapshot) = match event {
+            Event::RecordCost { cost, snapshot } => (cost, snapshot),
+            Event::RecordDynamicCost {
+                gas_cost,
+                memory_gas,
+                snapshot,
+                ..
+            } => {
+                let memory_cost = snapshot
+                    .as_ref()
+                    .map(|snapshot| memory_gas.saturating_sub(snapshot.memory_gas))
+                    .unwrap_or_default();
+                (gas_cost + memory_cost, snapshot)
+            }
+            _ => return,
+        };
+        let Some(snapshot) = snapshot else {
+            return;
+        };
+
+        let mut tracer = self.0.borrow_mut();
+        // Snapshot is taken before the cost is recorded
+        let used = snapshot.used_gas + snapshot.memory_gas;
+        let gas_left = snapshot.gas_limit.saturating_sub(used);
+
+        if !tracer.step_priced {
+            if let Some(log) = tracer.struct_logs.last_mut() {
+                log.gas = gas_left;
+            }
+            tracer.step_priced = true;
+        }
+        if let Some(log) = tracer.struct_logs.last_mut() {
+            log.gas_cost += cost;
+        }
+
+        tracer.gas_left = gas_left.saturating_sub(cost);
+        tracer.gas_used = used + cost;
+    }
+}
+
+fn opcode_name(opcode: u8) -> String {
+    let name = match opcode {
+        0x00 => "STOP",
+        0x01 => "ADD",
+        0x02 => "MUL",
+        0x03 => "SUB",
+        0x04 => "DIV",
+        0x05 => "SDIV",
+        0x06 => "MOD",
+        0x07 => "SMOD",
+        0x08 => "ADDMOD",
+        0x09 => "MULMOD",
+        0x0a => "EXP",
+        0x0b => "SIGNEXTEND",
+        0x10 => "LT",
+        0x11 => "GT",
+        0x12 => "SLT",
+        0x13 => "SGT",
+        0x14 => "EQ",
+        0x15 => "ISZERO",
+        0x16 => "AND",
+        0x17 => "OR",
+        0x18 => "XOR",
+        0x19 => "NOT",
+        0x1a => "BYTE",
+        0x1b => "SHL",
+        0x1c => "SHR",
+        0x1d => "SAR",
+        0x20 => "SHA3",
+        0x30 => "ADDRESS",
+        0x31 => "BALANCE",
+        0x32 => "ORIGIN",
+        0x33 => "CALLER",
+        0x34 => "CALLVALUE",
+        0x35 => "CALLDATALOAD",
+        0x36 => "CALLDATASIZE",
+        0x37 => "CALLDATACOPY",
+        0x38 => "CODESIZE",
+        0x39 => "CODECOPY",
+        0x3a => "GASPRICE",
+        0x3b => "EXTCODESIZE",
+        0x3c => "EXTCODECOPY",
+        0x3d => "RETURNDATASIZE",
+        0x3e => "RETURNDATACOPY",
+        0x3f => "EXTCODEHASH",
+        0x40 => "BLOCKHASH",
+        0x41 => "COINBASE",
+        0x42 => "TIMESTAMP",
+        0x43 => "NUMBER",
+        0x44 => "DIFFICULTY",
+        0x45 => "GASLIMIT",
+        0x46 => "CHAINID",
+        0x47 => "SELFBALANCE",
+        0x48 => "BASEFEE",
+        0x50 => "POP",
+        0x51 => "MLOAD",
+        0x52 => "MSTORE",
+        0x53 => "MSTORE8",
+        0x54 => "SLOAD",
+        0x55 => "SSTORE",
+        0x56 => "JUMP",
+        0x57 => "JUMPI",
+        0x58 => "PC",
+        0x59 => "MSIZE",
+        0x5a => "GAS",
+        0x5b => "JUMPDEST",
+        0x5f => "PUSH0",
+        0x60..=0x7f => return format!("PUSH{}", opcode - 0x5f),
+        0x80..=0x8f => return format!("DUP{}", opcode - 0x7f),
+        0x90..=0x9f => return format!("SWAP{}", opcode - 0x8f),
+        0xa0..=0xa4 => return format!("LOG{}", opcode - 0xa0),
+        0xf0 => "CREATE",
+        0xf1 => "CALL",
+        0xf2 => "CALLCODE",
+        0xf3 => "RETURN",
+        0xf4 => "DELEGATECALL",
+        0xf5 => "CREATE2",
+        0xfa => "STATICCALL",
+        0xfd => "REVERT",
+        0xfe => "INVALID",
+        0xff => "SELFDESTRUCT",
+        _ => return format!("opcode {:#04x} not defined", opcode),
+    };
+
+    name.to_string()
+}
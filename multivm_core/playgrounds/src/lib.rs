@@ -2,9 +2,9 @@ use std::collections::HashMap;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use multivm_primitives::{
-    k256::ecdsa::SigningKey, AccountId, Attachments, Block, ContractCall, ContractCallContext,
-    ContractResponse, Digest, EnvironmentContext, EvmAddress, MultiVmAccountId, SignedTransaction,
-    SupportedTransaction, Transaction,
+    k256::ecdsa::SigningKey, AccountCreationParams, AccountId, Attachments, Block, ContractCall,
+    ContractCallContext, ContractResponse, Digest, EnvironmentContext, EvmAddress,
+    MultiVmAccountId, SignedTransaction, SupportedTransaction, Transaction,
 };
 use multivm_runtime::{account::Account, MultivmNode};
 use rand::rngs::OsRng;
@@ -82,32 +82,44 @@ impl NodeHelper {
     fn create_super_account_now(&mut self) {
         let account_id = Self::super_account_id();
 
-        let latest_block = self.node.latest_block();
         let sk = self.keys.get(&account_id.into()).unwrap();
         let address: EvmAddress = (*sk.verifying_key()).into();
 
-        let tx = multivm_primitives::TransactionBuilder::new(
-            AccountId::system_meta_contract(),
-            vec![ContractCall::new(
+        self.node
+            .genesis_call(ContractCall::new(
                 "init_debug_account".into(),
                 &address,
                 100_000_000,
                 0,
-            )],
-            AccountId::system_meta_contract(),
-            &latest_block,
-        )
-        .build();
+            ))
+            .expect("Failed to create super account");
 
-        let tx = SignedTransaction {
-            transaction: tx,
-            signature: Default::default(),
-            attachments: None,
-            recovery_id: 0,
-        };
+        self.node
+            .genesis_call(ContractCall::new(
+                "set_registrar".into(),
+                &AccountId::from(Self::super_account_id()),
+                100_000_000,
+                0,
+            ))
+            .expect("Failed to set super account as registrar");
+
+        self.node
+            .genesis_call(ContractCall::new(
+                "set_fee_collector".into(),
+                &AccountId::from(Self::super_account_id()),
+                100_000_000,
+                0,
+            ))
+            .expect("Failed to set super account as fee collector");
 
-        self.node.add_tx(tx.into());
-        self.produce_block(true);
+        self.node
+            .genesis_call(ContractCall::new(
+                "set_account_creation_params".into(),
+                &AccountCreationParams::default(),
+                100_000_000,
+                0,
+            ))
+            .expect("Failed to set account creation params");
     }
 
     pub fn create_account(&mut self, multivm_account_id: &MultiVmAccountId) -> Digest {
@@ -118,7 +130,8 @@ impl NodeHelper {
         let latest_block = self.node.latest_block();
         let tx = create_account_tx(
             &latest_block,
-            multivm_account_id.clone(),
+            self.node.pending_nonce(&Self::super_account_id().into()),
+            Some(multivm_account_id.clone()),
             Self::super_account_id().into(),
             (*sk.verifying_key()).into(),
         );
@@ -126,7 +139,7 @@ impl NodeHelper {
         let tx =
             SignedTransaction::new(tx, self.keys.get(&Self::super_account_id().into()).unwrap());
 
-        self.node.add_tx(tx.into());
+        self.node.add_tx(tx.into()).expect("Transaction rejected");
 
         tx_hash
     }
@@ -140,14 +153,34 @@ impl NodeHelper {
 
         let tx = create_account_tx(
             &latest_block,
-            multivm_account_id.clone(),
+            self.node.pending_nonce(&Self::super_account_id().into()),
+            Some(multivm_account_id.clone()),
+            Self::super_account_id().into(),
+            address.clone(),
+        );
+        let tx =
+            SignedTransaction::new(tx, self.keys.get(&Self::super_account_id().into()).unwrap());
+
+        self.node.add_tx(tx.into()).expect("Transaction rejected");
+
+        address
+    }
+
+    /// Creates account known by the EVM address only
+    pub fn create_implicit_account(&mut self, address: EvmAddress) -> EvmAddress {
+        let latest_block = self.node.latest_block();
+
+        let tx = create_account_tx(
+            &latest_block,
+            self.node.pending_nonce(&Self::super_account_id().into()),
+            None,
             Self::super_account_id().into(),
             address.clone(),
         );
         let tx =
             SignedTransaction::new(tx, self.keys.get(&Self::super_account_id().into()).unwrap());
 
-        self.node.add_tx(tx.into());
+        self.node.add_tx(tx.into()).expect("Transaction rejected");
 
         address
     }
@@ -181,16 +214,90 @@ impl NodeHelper {
         let latest_block = self.node.latest_block();
         self.keys
             .insert(multivm_contract_id.clone().into(), key.clone());
-        let (tx, attachs) =
-            deploy_contract_tx(&latest_block, multivm_contract_id.clone().into(), code);
+        let (tx, attachs) = deploy_contract_tx(
+            &latest_block,
+            self.node.pending_nonce(&multivm_contract_id.clone().into()),
+            multivm_contract_id.clone().into(),
+            code,
+        );
         let tx_hash = tx.hash();
         let tx = SignedTransaction::new_with_attachments(tx, &key, attachs);
 
-        self.node.add_tx(tx.into());
+        self.node.add_tx(tx.into()).expect("Transaction rejected");
+
+        tx_hash
+    }
+
+    /// Uploads the contract image to the code store, returns its image ID
+    pub fn upload_code(&mut self, signer_id: &AccountId, code: Vec<u8>) -> [u32; 8] {
+        let latest_block = self.node.latest_block();
+        let image_id = image_id(&code).expect("Invalid contract ELF");
+        let (tx, attachs) = code_tx(
+            &latest_block,
+            self.node.pending_nonce(signer_id),
+            signer_id.clone(),
+            "upload_code",
+            code,
+            image_id,
+        );
+        let tx =
+            SignedTransaction::new_with_attachments(tx, self.keys.get(signer_id).unwrap(), attachs);
+
+        self.node.add_tx(tx.into()).expect("Transaction rejected");
+
+        image_id
+    }
+
+    /// Deploys the uploaded image to the contract account
+    pub fn deploy_from_image(
+        &mut self,
+        multivm_contract_id: &MultiVmAccountId,
+        image_id: [u32; 8],
+    ) -> Digest {
+        self.call_contract(
+            &multivm_contract_id.clone().into(),
+            &AccountId::system_meta_contract(),
+            ContractCall::new("deploy_from_image".to_string(), &image_id, 100_000_000, 0),
+        )
+    }
+
+    /// Replaces code of the deployed contract, the migration is called with the new code
+    pub fn upgrade_contract(
+        &mut self,
+        multivm_contract_id: &MultiVmAccountId,
+        code: Vec<u8>,
+        migration: Option<ContractCall>,
+    ) -> Digest {
+        let latest_block = self.node.latest_block();
+        let key = self.keys.get(&multivm_contract_id.clone().into()).unwrap();
+        let (tx, attachs) = upgrade_contract_tx(
+            &latest_block,
+            self.node.pending_nonce(&multivm_contract_id.clone().into()),
+            multivm_contract_id.clone().into(),
+            code,
+            migration,
+        );
+        let tx_hash = tx.hash();
+        let tx = SignedTransaction::new_with_attachments(tx, key, attachs);
+
+        self.node.add_tx(tx.into()).expect("Transaction rejected");
 
         tx_hash
     }
 
+    /// Declares Solidity ABI of the contract, so Ethereum wallets can call it
+    pub fn declare_abi(
+        &mut self,
+        multivm_contract_id: &MultiVmAccountId,
+        abi: Vec<String>,
+    ) -> Digest {
+        self.call_contract(
+            &multivm_contract_id.clone().into(),
+            &AccountId::system_meta_contract(),
+            ContractCall::new("declare_abi".to_string(), &abi, 100_000_000, 0),
+        )
+    }
+
     pub fn call_contract(
         &mut self,
         signer_id: &AccountId,
@@ -205,11 +312,14 @@ impl NodeHelper {
             signer_id.clone(),
             &latest_block,
         )
+        .set_nonce(self.node.pending_nonce(signer_id))
         .build();
 
         let tx_hash = tx.hash();
         let tx = SignedTransaction::new(tx, self.keys.get(signer_id).unwrap());
-        self.node.add_tx(SupportedTransaction::MultiVm(tx.into()));
+        self.node
+            .add_tx(SupportedTransaction::MultiVm(tx.into()))
+            .expect("Transaction rejected");
 
         tx_hash
     }
@@ -239,15 +349,25 @@ impl NodeHelper {
     }
 }
 
+/// Computes image ID of the contract ELF, as signed in the deployment request
+pub fn image_id(code: &[u8]) -> anyhow::Result<[u32; 8]> {
+    let program = risc0_zkvm::Program::load_elf(code, 0x10000000)?;
+    let image = risc0_zkvm::MemoryImage::new(&program, 0x400)?;
+    let image_id = image.compute_id().as_words().try_into()?;
+
+    Ok(image_id)
+}
+
 fn create_account_tx(
     latest_block: &Block,
-    multivm_account_id: MultiVmAccountId,
+    nonce: u64,
+    multivm_account_id: Option<MultiVmAccountId>,
     signer_id: AccountId,
     address: EvmAddress,
 ) -> Transaction {
     #[derive(BorshDeserialize, BorshSerialize)]
     struct AccountCreationRequest {
-        pub account_id: MultiVmAccountId,
+        pub account_id: Option<MultiVmAccountId>,
         pub address: EvmAddress,
     }
 
@@ -267,31 +387,86 @@ fn create_account_tx(
         signer_id,
         &latest_block,
     )
+    .set_nonce(nonce)
     .build()
 }
 
 fn deploy_contract_tx(
     latest_block: &Block,
+    nonce: u64,
+    account_id: AccountId,
+    code: Vec<u8>,
+) -> (Transaction, Attachments) {
+    let image_id = image_id(&code).expect("Invalid contract ELF");
+    code_tx(
+        latest_block,
+        nonce,
+        account_id,
+        "deploy_contract",
+        code,
+        image_id,
+    )
+}
+
+/// Transaction calling the system method with the image ID, the image is attached
+fn code_tx(
+    latest_block: &Block,
+    nonce: u64,
     account_id: AccountId,
+    method: &str,
     code: Vec<u8>,
+    image_id: [u32; 8],
 ) -> (Transaction, Attachments) {
     #[derive(BorshDeserialize, BorshSerialize)]
     struct ContractDeploymentRequest {
         pub image_id: [u32; 8],
     }
-    let program = risc0_zkvm::Program::load_elf(&code, 0x10000000).unwrap();
-    let image = risc0_zkvm::MemoryImage::new(&program, 0x400).unwrap();
-    let image_id: [u32; 8] = image.compute_id().as_words().try_into().unwrap();
 
     let mut contracts_images = HashMap::new();
     contracts_images.insert(image_id.clone(), code);
 
     let args = ContractDeploymentRequest { image_id };
 
+    let tx = multivm_primitives::TransactionBuilder::new(
+        AccountId::system_meta_contract(),
+        vec![ContractCall::new(method.to_string(), &args, 100_000_000, 0)],
+        account_id,
+        &latest_block,
+    )
+    .set_nonce(nonce)
+    .build();
+
+    let attachments = Attachments { contracts_images };
+
+    (tx, attachments)
+}
+
+fn upgrade_contract_tx(
+    latest_block: &Block,
+    nonce: u64,
+    account_id: AccountId,
+    code: Vec<u8>,
+    migration: Option<ContractCall>,
+) -> (Transaction, Attachments) {
+    #[derive(BorshDeserialize, BorshSerialize)]
+    struct ContractUpgradeRequest {
+        pub image_id: [u32; 8],
+        pub migration: Option<ContractCall>,
+    }
+    let image_id = image_id(&code).expect("Invalid contract ELF");
+
+    let mut contracts_images = HashMap::new();
+    contracts_images.insert(image_id.clone(), code);
+
+    let args = ContractUpgradeRequest {
+        image_id,
+        migration,
+    };
+
     let tx = multivm_primitives::TransactionBuilder::new(
         AccountId::system_meta_contract(),
         vec![ContractCall::new(
-            "deploy_contract".to_string(),
+            "upgrade_contract".to_string(),
             &args,
             100_000_000,
             0,
@@ -299,6 +474,7 @@ fn deploy_contract_tx(
         account_id,
         &latest_block,
     )
+    .set_nonce(nonce)
     .build();
 
     let attachments = Attachments { contracts_images };
@@ -1,15 +1,40 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use ethers_core::types::transaction::eip2718::TypedTransaction;
 use k256::ecdsa::{signature::Verifier, VerifyingKey};
 use serde::{Deserialize, Serialize};
 
 pub use k256;
 
+pub mod solidity_abi;
 pub mod syscalls;
 
 use std::collections::HashMap;
 
 pub const CHAIN_ID: u64 = 1044942;
 
+/// Domain separator of MultiVM transaction signatures
+pub const SIGNING_PREFIX: &[u8] = b"\x19MultiVM Signed Transaction:\n";
+
+/// Domain separator of relayer signatures of delegated transactions
+pub const DELEGATION_PREFIX: &[u8] = b"\x19MultiVM Delegated Transaction:\n";
+
+/// Version of the transaction signing payload
+pub const TRANSACTION_VERSION: u8 = 1;
+
+/// Base fee per gas of EVM transactions, in the smallest token units
+pub const BASE_FEE_PER_GAS: u128 = 1_000_000_000;
+
+pub const TOKEN_DECIMALS: u32 = 18;
+pub const ONE_TOKEN: u128 = 10u128.pow(TOKEN_DECIMALS);
+
+/// Balance locked per byte of account storage, 100 KB lock one token
+pub const STORAGE_PRICE_PER_BYTE: u128 = ONE_TOKEN / 100_000;
+
+/// Number of blocks the validator set stays the same
+pub const EPOCH_LENGTH: u64 = 100;
+/// Number of blocks unstaked tokens stay locked before they can be withdrawn
+pub const UNBONDING_PERIOD: u64 = 2 * EPOCH_LENGTH;
+
 #[derive(
     Serialize,
     Deserialize,
@@ -151,7 +176,6 @@ impl MultiVmAccountId {
         }
 
         if id
-            .to_lowercase()
             .chars()
             .any(|c| !MultiVmAccountId::ALLWED_SYMBOLS.contains(c))
         {
@@ -161,8 +185,24 @@ impl MultiVmAccountId {
             ));
         }
 
+        // Leading, trailing and consecutive dots make empty name parts
+        if id.split('.').any(str::is_empty) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "AccountID contains empty name part",
+            ));
+        }
+
         Ok(())
     }
+
+    /// Returns the parent name, e.g. `alice.multivm` for `sub.alice.multivm`,
+    /// `None` for names without dots
+    pub fn parent(&self) -> Option<MultiVmAccountId> {
+        self.0
+            .split_once('.')
+            .map(|(_, parent)| MultiVmAccountId(parent.to_string()))
+    }
 }
 
 impl TryFrom<String> for MultiVmAccountId {
@@ -267,6 +307,7 @@ pub struct Commitment {
     pub cross_calls_hashes: Vec<(Digest, Digest)>, // hashes of cross-calls (call, commitment)
     pub previous_account_root: Option<Digest>,
     pub new_account_root: Option<Digest>,
+    pub logs: Vec<EvmLog>,
 }
 
 impl Commitment {
@@ -279,6 +320,40 @@ impl Commitment {
     }
 }
 
+/// Event log emitted by an EVM contract.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    BorshSerialize,
+    BorshDeserialize,
+    Clone,
+    PartialEq,
+    Hash,
+    PartialOrd,
+    Eq,
+)]
+pub struct EvmLog {
+    pub address: EvmAddress,
+    pub topics: Vec<Digest>,
+    pub data: Vec<u8>,
+}
+
+/// Returns the 2048-bit bloom filter of the given logs addresses and topics
+pub fn logs_bloom<'a>(logs: impl IntoIterator<Item = &'a EvmLog>) -> Vec<u8> {
+    use ethers_core::abi::ethereum_types::BloomInput;
+
+    let mut bloom = ethers_core::types::Bloom::zero();
+    for log in logs {
+        bloom.accrue(BloomInput::Raw(&log.address.0));
+        for topic in &log.topics {
+            bloom.accrue(BloomInput::Raw(topic));
+        }
+    }
+
+    bloom.as_bytes().to_vec()
+}
+
 #[derive(
     Serialize,
     Deserialize,
@@ -361,14 +436,35 @@ impl EthereumTransactionRequest {
         Self(bytes)
     }
 
+    /// Decodes a signed legacy, EIP-2930 or EIP-1559 transaction, recovering its sender
     pub fn decode(
         &self,
-    ) -> (
-        ethers_core::types::TransactionRequest,
-        ethers_core::types::Signature,
-    ) {
+    ) -> Result<
+        (TypedTransaction, ethers_core::types::Signature),
+        ethers_core::types::transaction::eip2718::TypedTransactionError,
+    > {
         let rlp = ethers_core::utils::rlp::Rlp::new(&self.0);
-        ethers_core::types::TransactionRequest::decode_signed_rlp(&rlp).unwrap()
+        TypedTransaction::decode_signed(&rlp)
+    }
+
+    /// Returns the price per gas paid by the sender under the given base fee,
+    /// or `None` if the transaction doesn't cover the base fee
+    pub fn effective_gas_price(tx: &TypedTransaction, base_fee_per_gas: u128) -> Option<u128> {
+        let base_fee = ethers_core::types::U256::from(base_fee_per_gas);
+        let gas_price = match tx {
+            TypedTransaction::Eip1559(tx) => {
+                let max_fee = tx.max_fee_per_gas.unwrap_or_default();
+                let priority_fee = tx.max_priority_fee_per_gas.unwrap_or_default();
+                max_fee.min(base_fee.saturating_add(priority_fee))
+            }
+            _ => tx.gas_price().unwrap_or_default(),
+        };
+
+        if gas_price < base_fee {
+            return None;
+        }
+
+        gas_price.try_into().ok()
     }
 }
 
@@ -377,6 +473,7 @@ impl EthereumTransactionRequest {
 pub enum SupportedTransaction {
     MultiVm(SignedTransaction),
     Evm(EthereumTransactionRequest),
+    Delegated(DelegatedTransaction),
 }
 
 impl SupportedTransaction {
@@ -384,6 +481,7 @@ impl SupportedTransaction {
         match self {
             Self::MultiVm(tx) => tx.transaction.hash(),
             Self::Evm(tx) => ethers_core::utils::keccak256::<Vec<u8>>(tx.0.clone().into()),
+            Self::Delegated(tx) => tx.hash(),
         }
     }
 
@@ -391,6 +489,9 @@ impl SupportedTransaction {
         match self {
             Self::MultiVm(tx) => tx.transaction.receiver_id == AccountId::system_meta_contract(),
             SupportedTransaction::Evm(_) => true,
+            Self::Delegated(tx) => {
+                tx.transaction.transaction.receiver_id == AccountId::system_meta_contract()
+            }
         }
     }
 
@@ -398,10 +499,12 @@ impl SupportedTransaction {
         match self {
             Self::MultiVm(tx) => tx.transaction.signer_id.clone(),
             SupportedTransaction::Evm(tx) => {
-                let (tx_request, _sig) = tx.decode();
-                let from = tx_request.from.unwrap();
+                let (tx_request, _sig) = tx.decode().expect("Invalid EVM transaction");
+                let from = *tx_request.from().expect("EVM transaction without sender");
                 AccountId::Evm(from.into())
             }
+            // Relayer only pays the fees
+            Self::Delegated(tx) => tx.transaction.transaction.signer_id.clone(),
         }
     }
 }
@@ -463,6 +566,15 @@ impl Transaction {
         borsh::to_vec(self).unwrap()
     }
 
+    /// Payload signed by the signer: prefix, big-endian chain id, version and transaction hash
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let mut payload = SIGNING_PREFIX.to_vec();
+        payload.extend(CHAIN_ID.to_be_bytes());
+        payload.push(TRANSACTION_VERSION);
+        payload.extend(self.hash());
+        payload
+    }
+
     pub fn context(
         &self,
         call_index: usize,
@@ -519,18 +631,23 @@ pub struct SignedTransaction {
     pub transaction: Transaction,
     pub signature: Vec<u8>,
     pub recovery_id: u8,
+    /// Signatures of other keys of a multisig signer account
+    pub cosignatures: Vec<Cosignature>,
     /// Unsigned attachments
     pub attachments: Option<Attachments>,
 }
 
 impl SignedTransaction {
     pub fn new(transaction: Transaction, private_key: &k256::ecdsa::SigningKey) -> Self {
-        let (signature, recovery_id) = private_key.sign_recoverable(&transaction.bytes()).unwrap();
+        let (signature, recovery_id) = private_key
+            .sign_recoverable(&transaction.signing_payload())
+            .unwrap();
 
         Self {
             transaction,
             signature: signature.to_vec(),
             recovery_id: recovery_id.to_byte(),
+            cosignatures: vec![],
             attachments: None,
         }
     }
@@ -540,52 +657,313 @@ impl SignedTransaction {
         private_key: &k256::ecdsa::SigningKey,
         attachments: Attachments,
     ) -> Self {
-        let (signature, recovery_id) = private_key.sign_recoverable(&transaction.bytes()).unwrap();
+        let (signature, recovery_id) = private_key
+            .sign_recoverable(&transaction.signing_payload())
+            .unwrap();
         Self {
             transaction,
             signature: signature.to_vec(),
             recovery_id: recovery_id.to_byte(),
+            cosignatures: vec![],
             attachments: Some(attachments),
         }
     }
 
-    pub fn verify(&self, address: EvmAddress) -> bool {
-        let Some(public_key) = self.recover() else {
-            return false;
+    /// Adds signature of another multisig key
+    pub fn cosign(mut self, private_key: &k256::ecdsa::SigningKey) -> Self {
+        let (signature, recovery_id) = private_key
+            .sign_recoverable(&self.transaction.signing_payload())
+            .unwrap();
+        self.cosignatures.push(Cosignature {
+            signature: signature.to_vec(),
+            recovery_id: recovery_id.to_byte(),
+        });
+        self
+    }
+
+    /// Checks that the transaction is signed by the key of the given address
+    pub fn verify(&self, address: EvmAddress) -> Result<(), SignatureError> {
+        let recovered = EvmAddress::from(self.recover()?);
+        if recovered != address {
+            return Err(SignatureError::SignerMismatch {
+                expected: address,
+                recovered,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns verified public key of the primary signature
+    pub fn recover(&self) -> Result<k256::ecdsa::VerifyingKey, SignatureError> {
+        recover_signer(
+            &self.transaction.signing_payload(),
+            &self.signature,
+            self.recovery_id,
+        )
+    }
+
+    /// Returns addresses of all the transaction signers, the primary one first
+    pub fn signers(&self) -> Result<Vec<EvmAddress>, SignatureError> {
+        let payload = self.transaction.signing_payload();
+
+        std::iter::once((&self.signature, self.recovery_id))
+            .chain(
+                self.cosignatures
+                    .iter()
+                    .map(|cosignature| (&cosignature.signature, cosignature.recovery_id)),
+            )
+            .map(|(signature, recovery_id)| {
+                recover_signer(&payload, signature, recovery_id).map(EvmAddress::from)
+            })
+            .collect()
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Cosignature {
+    pub signature: Vec<u8>,
+    pub recovery_id: u8,
+}
+
+/// User signed transaction relayed by another account, which pays the gas fees.
+/// The inner transaction nonce has to match the user account nonce
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct DelegatedTransaction {
+    pub transaction: SignedTransaction,
+    pub relayer_id: AccountId,
+    pub signature: Vec<u8>,
+    pub recovery_id: u8,
+}
+
+impl DelegatedTransaction {
+    pub fn new(
+        transaction: SignedTransaction,
+        relayer_id: AccountId,
+        private_key: &k256::ecdsa::SigningKey,
+    ) -> Self {
+        let mut delegated = Self {
+            transaction,
+            relayer_id,
+            signature: vec![],
+            recovery_id: 0,
         };
+        let (signature, recovery_id) = private_key
+            .sign_recoverable(&delegated.signing_payload())
+            .unwrap();
+        delegated.signature = signature.to_vec();
+        delegated.recovery_id = recovery_id.to_byte();
+        delegated
+    }
+
+    /// Hash of the inner transaction hash and the relayer
+    pub fn hash(&self) -> Digest {
+        use sha2::{Digest as _, Sha256};
+        let bytes =
+            borsh::to_vec(&(self.transaction.transaction.hash(), &self.relayer_id)).unwrap();
+        Sha256::digest(bytes).try_into().unwrap()
+    }
 
-        let signature = k256::ecdsa::Signature::from_slice(&self.signature).unwrap();
+    /// Payload signed by the relayer: prefix, big-endian chain id, version and delegation hash
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let mut payload = DELEGATION_PREFIX.to_vec();
+        payload.extend(CHAIN_ID.to_be_bytes());
+        payload.push(TRANSACTION_VERSION);
+        payload.extend(self.hash());
+        payload
+    }
 
-        let signature_valid = public_key
-            .verify(&self.transaction.bytes(), &signature)
-            .is_ok();
+    /// Returns verified public key of the relayer signature
+    pub fn recover_relayer(&self) -> Result<k256::ecdsa::VerifyingKey, SignatureError> {
+        recover_signer(&self.signing_payload(), &self.signature, self.recovery_id)
+    }
+}
 
-        let recovered_address = EvmAddress::from(public_key);
-        let address_valid = recovered_address == address;
+/// Recovers the public key of the signature and verifies the signature with it
+fn recover_signer(
+    payload: &[u8],
+    signature: &[u8],
+    recovery_id: u8,
+) -> Result<k256::ecdsa::VerifyingKey, SignatureError> {
+    let signature = k256::ecdsa::Signature::from_slice(signature)
+        .map_err(|_| SignatureError::MalformedSignature)?;
+    let recovery_id = k256::ecdsa::RecoveryId::from_byte(recovery_id)
+        .ok_or(SignatureError::InvalidRecoveryId(recovery_id))?;
+
+    let public_key = k256::ecdsa::VerifyingKey::recover_from_msg(payload, &signature, recovery_id)
+        .map_err(|_| SignatureError::RecoveryFailed)?;
+
+    public_key
+        .verify(payload, &signature)
+        .map_err(|_| SignatureError::InvalidSignature)?;
+
+    Ok(public_key)
+}
 
-        if !signature_valid {
-            panic!("signature is invalid");
-        }
+/// Reason of MultiVM transaction signature rejection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// Signature is not a valid 64-byte `r || s` pair
+    MalformedSignature,
+    InvalidRecoveryId(u8),
+    /// Public key can't be recovered from the signature
+    RecoveryFailed,
+    /// Signature doesn't match the signing payload
+    InvalidSignature,
+    /// Transaction is signed by a key of another account
+    SignerMismatch {
+        expected: EvmAddress,
+        recovered: EvmAddress,
+    },
+}
 
-        if !address_valid {
-            panic!("address is invalid: {} != {}", recovered_address, address)
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MalformedSignature => write!(f, "Malformed signature"),
+            Self::InvalidRecoveryId(id) => write!(f, "Invalid recovery id: {}", id),
+            Self::RecoveryFailed => write!(f, "Failed to recover public key"),
+            Self::InvalidSignature => write!(f, "Invalid signature"),
+            Self::SignerMismatch {
+                expected,
+                recovered,
+            } => write!(
+                f,
+                "Signer mismatch: expected {}, recovered {}",
+                expected, recovered
+            ),
         }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Key allowed to sign MultiVM transactions of an account
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AccessKey {
+    /// Address of the key public key
+    pub address: EvmAddress,
+    pub permission: AccessKeyPermission,
+}
 
-        signature_valid && address_valid
+impl AccessKey {
+    pub fn full_access(address: EvmAddress) -> Self {
+        Self {
+            address,
+            permission: AccessKeyPermission::FullAccess,
+        }
     }
+}
 
-    pub fn recover(&self) -> Option<k256::ecdsa::VerifyingKey> {
-        let signature = k256::ecdsa::Signature::from_slice(&self.signature).unwrap();
-        let bytes = &self.transaction.bytes();
-        k256::ecdsa::VerifyingKey::recover_from_msg(
-            &bytes,
-            &signature,
-            self.recovery_id.try_into().unwrap(),
-        )
-        .ok()
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum AccessKeyPermission {
+    FullAccess,
+    /// Allows calls of the contract methods without deposit, any method if `methods` is empty.
+    /// Gas attached to the calls is taken from the allowance
+    FunctionCall {
+        contract_id: AccountId,
+        methods: Vec<String>,
+        gas_allowance: u64,
+    },
+}
+
+/// Keys of a multisig account, transactions need signatures of `threshold` of them
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct Multisig {
+    pub keys: Vec<EvmAddress>,
+    pub threshold: u32,
+}
+
+/// Contract image in the code store, shared by all the contracts deployed from it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct StoredCode {
+    pub uploader: AccountId,
+    /// Number of contracts running the image, it is removed once the last one stops using it
+    pub refs: u64,
+}
+
+/// Hex of the image ID bytes, the code store key
+pub fn image_id_hex(image_id: &[u32; 8]) -> String {
+    image_id
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Cost of account creation, set at genesis
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AccountCreationParams {
+    /// Balance moved from the creator to the new account
+    pub initial_balance: u128,
+    /// Fee paid by the creator to the fee collector
+    pub creation_fee: u128,
+}
+
+impl Default for AccountCreationParams {
+    fn default() -> Self {
+        Self {
+            initial_balance: 1_000 * ONE_TOKEN,
+            creation_fee: 0,
+        }
     }
 }
 
+/// Change of the contract storage size, applied by the node on storage writes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct StorageUsageUpdate {
+    pub account_id: AccountId,
+    /// Bytes added, negative if freed
+    pub delta: i64,
+}
+
+/// Account staking to produce blocks
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct Validator {
+    pub account_id: AccountId,
+    /// SEC1 encoded secp256k1 key signing the blocks
+    pub public_key: Vec<u8>,
+    pub stake: u128,
+    /// Share of the rewards kept by the validator, in basis points
+    pub commission: u16,
+}
+
+/// Unstaked tokens waiting for the end of the unbonding period
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct Unbonding {
+    pub amount: u128,
+    /// Height of the first block the tokens can be withdrawn in
+    pub unlock_height: u64,
+}
+
+/// Code deployed to a MultiVM contract, the first one is the initial deployment
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct CodeVersion {
+    pub image_id: [u32; 8],
+    /// Height of the block the code is deployed in
+    pub block_height: u64,
+}
+
+/// Stored Borsh encoded, so databases written before a field was added (`logs_bloom`,
+/// `producer`) can't be read and have to be recreated
 #[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct Block {
     pub height: u64,
@@ -596,10 +974,121 @@ pub struct Block {
     pub timestamp: u64,
     pub txs: Vec<SupportedTransaction>,
     pub call_outputs: HashMap<Digest, ContractResponse>,
+    pub receipts: Vec<Receipt>,
+    pub logs_bloom: Vec<u8>,
+    /// Validator chosen to produce the block from the epoch snapshot, `None` while nobody stakes
+    pub producer: Option<AccountId>,
     // pub execution_outcomes: HashMap<Digest, ExecutionOutcome>,
     // pub sessions: HashMap<Digest, String>, // TODO: replace json to struct
 }
 
+impl Block {
+    pub fn receipt(&self, tx_hash: &Digest) -> Option<&Receipt> {
+        self.receipts
+            .iter()
+            .find(|receipt| &receipt.transaction_hash == tx_hash)
+    }
+
+    pub fn logs(&self) -> impl Iterator<Item = (&Receipt, &ReceiptLog)> {
+        self.receipts
+            .iter()
+            .flat_map(|receipt| receipt.logs.iter().map(move |log| (receipt, log)))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct ReceiptLog {
+    pub log_index: u64,
+    pub log: EvmLog,
+}
+
+/// Result of a transaction included into a block
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct Receipt {
+    pub transaction_hash: Digest,
+    pub transaction_index: u64,
+    pub status: bool,
+    pub logs: Vec<ReceiptLog>,
+    pub logs_bloom: Vec<u8>,
+    pub call_trace: Option<CallTrace>,
+}
+
+impl Receipt {
+    pub fn new(
+        transaction_hash: Digest,
+        transaction_index: u64,
+        status: bool,
+        logs: Vec<ReceiptLog>,
+        call_trace: Option<CallTrace>,
+    ) -> Self {
+        let logs_bloom = logs_bloom(logs.iter().map(|log| &log.log));
+        Self {
+            transaction_hash,
+            transaction_index,
+            status,
+            logs,
+            logs_bloom,
+            call_trace,
+        }
+    }
+}
+
+/// Node of the transaction call tree, spanning system, Rust and EVM contracts
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct CallTrace {
+    pub contract_id: AccountId,
+    pub method: String,
+    pub args_hash: Digest,
+    pub caller: AccountId,
+    /// Gas attached to the call
+    pub gas: u64,
+    /// zkVM cycles used by the call, cross-calls excluded
+    pub cycles: u64,
+    pub response: ContractResponse,
+    pub storage_reads: Vec<StorageKey>,
+    pub storage_writes: Vec<StorageKey>,
+    pub logs: Vec<String>,
+    pub calls: Vec<CallTrace>,
+}
+
+/// Call frame of the EVM execution trace, as in geth `callTracer`
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize)]
+pub struct EvmCallFrame {
+    pub call_type: String,
+    pub from: [u8; 20],
+    pub to: [u8; 20],
+    /// Big-endian U256
+    pub value: [u8; 32],
+    pub gas: u64,
+    pub gas_used: u64,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    pub error: Option<String>,
+    pub calls: Vec<EvmCallFrame>,
+}
+
+/// Executed opcode of the EVM execution trace, as in geth `structLogger`
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+pub struct EvmStructLog {
+    pub pc: u64,
+    pub op: String,
+    pub gas: u64,
+    pub gas_cost: u64,
+    pub depth: u64,
+    /// Big-endian U256 words, from bottom to top
+    pub stack: Vec<[u8; 32]>,
+}
+
+/// Trace of the EVM execution, gathered in non-proving debug mode only
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+pub struct EvmTrace {
+    pub call: EvmCallFrame,
+    pub struct_logs: Vec<EvmStructLog>,
+    pub gas_used: u64,
+    pub failed: bool,
+    pub return_value: Vec<u8>,
+}
+
 #[derive(Serialize, Deserialize, Debug, BorshSerialize, BorshDeserialize)]
 pub struct TransactionBuilder {
     pub receiver_id: AccountId,
@@ -0,0 +1,151 @@
+use eth_primitive_types::{H160, U256};
+use ethers_core::abi::{Abi, Function, ParamType, Token};
+
+/// Account storage key of the Solidity ABI declared by a MultiVM contract
+pub const ABI_KEY: &str = "solidity_abi";
+
+/// Parses human-readable ABI, e.g. `function balance_of(address) view returns (uint128)`.
+/// Function names have to match the contract method names.
+pub fn parse(signatures: &[String]) -> Abi {
+    let signatures: Vec<&str> = signatures.iter().map(String::as_str).collect();
+    ethers_core::abi::parse_abi(&signatures).expect("Invalid Solidity ABI") // TODO: handle error
+}
+
+/// Finds the function by 4-byte selector and converts its ABI encoded args to Borsh
+pub fn decode_call<'a>(abi: &'a Abi, data: &[u8]) -> (&'a Function, Vec<u8>) {
+    if data.len() < 4 {
+        panic!("Call data is too short for a function selector"); // TODO: handle error
+    }
+    let (selector, input) = data.split_at(4);
+
+    let function = abi
+        .functions()
+        .find(|function| function.short_signature() == selector)
+        .expect("Function selector not found in contract ABI"); // TODO: handle error
+
+    let tokens = function
+        .decode_input(input)
+        .expect("Invalid ABI encoded arguments"); // TODO: handle error
+
+    let mut args = Vec::new();
+    for (param, token) in function.inputs.iter().zip(tokens) {
+        write_borsh(&param.kind, token, &mut args);
+    }
+
+    (function, args)
+}
+
+/// Converts Borsh encoded method output to ABI encoded function return value
+pub fn encode_output(function: &Function, output: &[u8]) -> Vec<u8> {
+    let mut output = output;
+    let tokens: Vec<Token> = function
+        .outputs
+        .iter()
+        .map(|param| read_borsh(&param.kind, &mut output))
+        .collect();
+
+    ethers_core::abi::encode(&tokens)
+}
+
+fn write_borsh(kind: &ParamType, token: Token, out: &mut Vec<u8>) {
+    match (kind, token) {
+        (ParamType::Address, Token::Address(address)) => out.extend(address.as_bytes()),
+        (ParamType::Uint(bits), Token::Uint(value)) => {
+            if value.bits() > *bits {
+                panic!("Value {} is out of uint{} range", value, bits); // TODO: handle error
+            }
+            write_borsh_int(value, *bits, out);
+        }
+        (ParamType::Int(bits), Token::Int(value)) => {
+            // Two's complement fits if the bits above the sign bit are the same as the sign bit
+            let magnitude = if value.bit(255) { !value } else { value };
+            if magnitude.bits() >= *bits {
+                panic!("Value {} is out of int{} range", value, bits); // TODO: handle error
+            }
+            write_borsh_int(value, *bits, out);
+        }
+        (ParamType::Bool, Token::Bool(value)) => out.push(value as u8),
+        (ParamType::String, Token::String(value)) => write_borsh_bytes(value.as_bytes(), out),
+        (ParamType::Bytes, Token::Bytes(value)) => write_borsh_bytes(&value, out),
+        (ParamType::FixedBytes(_), Token::FixedBytes(value)) => out.extend(value),
+        (ParamType::Array(kind), Token::Array(tokens)) => {
+            out.extend((tokens.len() as u32).to_le_bytes());
+            tokens
+                .into_iter()
+                .for_each(|token| write_borsh(kind, token, out));
+        }
+        (ParamType::FixedArray(kind, _), Token::FixedArray(tokens)) => tokens
+            .into_iter()
+            .for_each(|token| write_borsh(kind, token, out)),
+        (ParamType::Tuple(kinds), Token::Tuple(tokens)) => kinds
+            .iter()
+            .zip(tokens)
+            .for_each(|(kind, token)| write_borsh(kind, token, out)),
+        (kind, token) => panic!("Token {:?} doesn't match ABI type {}", token, kind),
+    }
+}
+
+/// Writes the low `bits` of the value, the caller checks that nothing is truncated
+fn write_borsh_int(value: U256, bits: usize, out: &mut Vec<u8>) {
+    let mut bytes = [0u8; 32];
+    value.to_little_endian(&mut bytes);
+    out.extend(&bytes[..bits / 8]);
+}
+
+fn write_borsh_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    out.extend((bytes.len() as u32).to_le_bytes());
+    out.extend(bytes);
+}
+
+fn read_borsh(kind: &ParamType, input: &mut &[u8]) -> Token {
+    match kind {
+        ParamType::Address => Token::Address(H160::from_slice(take(input, 20))),
+        ParamType::Uint(bits) => Token::Uint(U256::from_little_endian(take(input, bits / 8))),
+        ParamType::Int(bits) => {
+            let bytes = take(input, bits / 8);
+            let negative = bytes
+                .last()
+                .map(|byte| byte & 0x80 != 0)
+                .unwrap_or_default();
+            // Sign extension up to 256 bits
+            let mut extended = [if negative { 0xff } else { 0 }; 32];
+            extended[..bytes.len()].copy_from_slice(bytes);
+            Token::Int(U256::from_little_endian(&extended))
+        }
+        ParamType::Bool => Token::Bool(take(input, 1)[0] != 0),
+        ParamType::String => {
+            let bytes = read_borsh_bytes(input);
+            Token::String(String::from_utf8(bytes).expect("Invalid UTF-8 string in output"))
+        }
+        ParamType::Bytes => Token::Bytes(read_borsh_bytes(input)),
+        ParamType::FixedBytes(size) => Token::FixedBytes(take(input, *size).to_vec()),
+        ParamType::Array(kind) => {
+            let len = read_borsh_len(input);
+            Token::Array((0..len).map(|_| read_borsh(kind, input)).collect())
+        }
+        ParamType::FixedArray(kind, len) => {
+            Token::FixedArray((0..*len).map(|_| read_borsh(kind, input)).collect())
+        }
+        ParamType::Tuple(kinds) => {
+            Token::Tuple(kinds.iter().map(|kind| read_borsh(kind, input)).collect())
+        }
+    }
+}
+
+fn read_borsh_len(input: &mut &[u8]) -> usize {
+    u32::from_le_bytes(take(input, 4).try_into().unwrap()) as usize
+}
+
+fn read_borsh_bytes(input: &mut &[u8]) -> Vec<u8> {
+    let len = read_borsh_len(input);
+    take(input, len).to_vec()
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> &'a [u8] {
+    if input.len() < len {
+        panic!("Output doesn't match contract ABI"); // TODO: handle error
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    head
+}
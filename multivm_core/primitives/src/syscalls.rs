@@ -40,6 +40,41 @@ pub struct SetStorageRequest {
     pub storage: Vec<u8>,
 }
 
+// Writes all the storage changed by the contract call. If the storage size changes, the host
+// updates the contract storage usage through the system meta-contract and responds with
+// `Option<Commitment>` of that call. Nothing is written if the update fails
+risc0_zkvm_platform::declare_syscall!(pub COMMIT_STORAGE_CALL);
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct CommitStorageRequest {
+    pub updates: Vec<SetStorageRequest>,
+}
+
+risc0_zkvm_platform::declare_syscall!(pub DELETE_STORAGE_CALL);
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct DeleteStorageRequest {
+    pub key: StorageKey,
+}
+
+// Stores the attached contract image in the code store
+risc0_zkvm_platform::declare_syscall!(pub UPLOAD_CODE_CALL);
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct UploadCodeRequest {
+    pub image_id: [u32; 8],
+}
+
+// Removes the image from the code store, once no contract uses it
+risc0_zkvm_platform::declare_syscall!(pub DELETE_CODE_CALL);
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct DeleteCodeRequest {
+    pub image_id: [u32; 8],
+}
+
+// Points the account to the image from the code store. Contracts deploy to their
+// sub-accounts only, the request is routed through the system meta-contract
 risc0_zkvm_platform::declare_syscall!(pub DEPLOY_CONTRACT_CALL);
 
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
@@ -47,3 +82,9 @@ pub struct DeployContractRequest {
     pub account_id: AccountId,
     pub image_id: [u32; 8],
 }
+
+// Sends `EvmTrace` of the traced execution to the host
+risc0_zkvm_platform::declare_syscall!(pub EVM_TRACE_CALL);
+
+// Responds with `u64` zkVM cycles used by the last cross-contract call, its cross-calls included
+risc0_zkvm_platform::declare_syscall!(pub CALL_CYCLES_CALL);
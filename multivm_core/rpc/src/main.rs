@@ -28,6 +28,10 @@ struct NodeOptions {
 
     #[arg(short, long, default_value_t = 8080)]
     port: u16,
+
+    /// Trace EVM executions for debug_* methods, requires evm-tracing feature
+    #[arg(long)]
+    trace: bool,
 }
 
 #[tokio::main]
@@ -36,6 +40,6 @@ async fn main() -> anyhow::Result<()> {
 
     let options = NodeOptions::parse();
 
-    let server = MultivmServer::new(options.db_path);
+    let server = MultivmServer::new(options.db_path, options.trace);
     server.start(options.port).await
 }
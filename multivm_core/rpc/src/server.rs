@@ -4,35 +4,85 @@ use std::{
     sync::{Arc, Mutex, MutexGuard, PoisonError},
 };
 
-use eth_primitive_types::H160;
-use ethers_core::k256::ecdsa::SigningKey;
+use eth_primitive_types::{H160, H256, U256};
+use ethers_core::types::{BlockNumber, Filter};
 use hyper::Method;
 use jsonrpsee::server::Server;
+use jsonrpsee::types::{error::INVALID_PARAMS_CODE, ErrorObject, ErrorObjectOwned};
 use jsonrpsee::RpcModule;
 use lazy_static::lazy_static;
 use multivm_primitives::{
-    AccountId, EthereumTransactionRequest, EvmAddress, MultiVmAccountId, SupportedTransaction,
+    AccountId, DelegatedTransaction, Digest, EthereumTransactionRequest, EvmAddress,
+    MultiVmAccountId, SignedTransaction, SupportedTransaction, CHAIN_ID,
 };
-use multivm_runtime::viewer::{EvmCall, SupportedView};
-use playgrounds::NodeHelper;
+use multivm_runtime::{
+    account::Executable,
+    viewer::{EvmCall, SupportedView},
+};
+use playgrounds::{image_id, NodeHelper};
 use serde_json::json;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{error, info};
 
-use crate::utils::{EthBlockOutput, EthTransaction, EthTransactionReceipt, From0x, To0x};
+use crate::utils::{
+    block_height, filter_matches, format_trace, multivm_code, EthBlockOutput, EthLog,
+    EthTransaction, EthTransactionReceipt, From0x, To0x,
+};
 
-static CHAIN_ID: u64 = 1044942;
 static INCORRECT_ARGS: &str = "\n🚨🚨🚨Incorrect arguments🚨🚨🚨\n";
+/// Gas of the plain value transfer
+const TRANSFER_GAS: u64 = 21_000;
+/// Gas limit suggested for calls and deployments, only the used gas is charged
+const CALL_GAS_ESTIMATE: u64 = 30_000_000;
+
+fn invalid_params(message: impl ToString) -> ErrorObjectOwned {
+    ErrorObject::owned(INVALID_PARAMS_CODE, message.to_string(), None::<()>)
+}
+
+/// Parses either Borsh encoded transaction as a hex string or its JSON representation
+fn parse_transaction<T>(value: serde_json::Value) -> Result<T, ErrorObjectOwned>
+where
+    T: borsh::BorshDeserialize + serde::de::DeserializeOwned,
+{
+    match value {
+        serde_json::Value::String(data) => hex::decode(data.trim_start_matches("0x"))
+            .map_err(|error| error.to_string())
+            .and_then(|data| borsh::from_slice(&data).map_err(|error| error.to_string())),
+        value => serde_json::from_value(value).map_err(|error| error.to_string()),
+    }
+    .map_err(|error| {
+        error!("Invalid transaction {:#?}", error);
+        invalid_params(format!("Invalid transaction: {}", error))
+    })
+}
+
+struct LogFilter {
+    filter: Filter,
+    last_polled_height: u64,
+}
+
+#[derive(Default)]
+struct LogFilters {
+    next_id: u64,
+    filters: HashMap<u64, LogFilter>,
+}
 
 #[derive(Clone)]
 pub struct MultivmServer {
     helper: Arc<Mutex<NodeHelper>>,
+    filters: Arc<Mutex<LogFilters>>,
 }
 
 impl MultivmServer {
-    pub fn new(db_path: Option<String>) -> Self {
+    pub fn new(db_path: Option<String>, trace: bool) -> Self {
+        let mut helper = NodeHelper::new(db_path);
+        if trace {
+            helper.node.enable_tracing();
+        }
+
         Self {
-            helper: Arc::new(Mutex::new(NodeHelper::new(db_path))),
+            helper: Arc::new(Mutex::new(helper)),
+            filters: Default::default(),
         }
     }
 
@@ -40,6 +90,37 @@ impl MultivmServer {
         helper.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
+    /// Returns the height of the state requested by the block parameter, `None` for the latest
+    fn state_height(helper: &NodeHelper, block: Option<&BlockNumber>) -> Option<u64> {
+        let latest = helper.node.latest_block().height;
+        let height = block_height(block, latest);
+        (height < latest).then_some(height)
+    }
+
+    fn lock_filters<'a>(filters: &'a Arc<Mutex<LogFilters>>) -> MutexGuard<'a, LogFilters> {
+        filters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns logs matching the filter from blocks in the given range (inclusive)
+    fn logs(helper: &NodeHelper, filter: &Filter, from: u64, to: u64) -> Vec<EthLog> {
+        let to = to.min(helper.node.latest_block().height);
+        (from..=to)
+            .filter_map(|height| helper.node.block_by_height(height))
+            .filter(|block| {
+                filter
+                    .get_block_hash()
+                    .map_or(true, |hash| hash.0 == block.hash)
+            })
+            .flat_map(|block| {
+                block
+                    .logs()
+                    .filter(|(_, log)| filter_matches(filter, &log.log))
+                    .map(|(receipt, log)| EthLog::from(log, receipt, &block))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
     pub async fn start(&self, port: u16) -> anyhow::Result<()> {
         let cors = CorsLayer::new()
             .allow_methods([Method::POST, Method::OPTIONS])
@@ -114,16 +195,21 @@ impl MultivmServer {
         module.register_method("eth_getTransactionReceipt", move |params, _| {
             let hash = params.sequence().next::<String>().expect(INCORRECT_ARGS);
             info!("eth_getTransactionReceipt: \n\r{:#?}", hash);
+            let tx_hash: Option<Digest> = hex::decode(hash.trim_start_matches("0x"))
+                .ok()
+                .and_then(|hash| hash.try_into().ok());
+
             let helper = Self::lock(&helper);
-            for height in 1..=helper.node.latest_block().height {
-                let block = helper.node.block_by_height(height).unwrap();
-                for tx in block.txs.clone() {
-                    if tx.hash().to_0x() == hash {
-                        let receipt = EthTransactionReceipt::from(&tx, hash, &block);
-                        info!("Response: {:#?}", receipt);
-                        return json!(receipt);
-                    }
-                }
+            let block = tx_hash.and_then(|tx_hash| helper.node.block_by_transaction(&tx_hash));
+            if let Some(block) = block {
+                let tx = block
+                    .txs
+                    .iter()
+                    .find(|tx| Some(tx.hash()) == tx_hash)
+                    .expect("Indexed transaction not found in block");
+                let receipt = EthTransactionReceipt::from(tx, hash, &block);
+                info!("Response: {:#?}", receipt);
+                return json!(receipt);
             }
             info!("Response: {{}}");
             return json!([]);
@@ -183,13 +269,80 @@ impl MultivmServer {
             info!("eth_gasPrice");
             "0x1dfd14000"
         })?;
-        module.register_method("eth_getCode", |params, _| {
+        module.register_method("eth_maxPriorityFeePerGas", |_, _| {
+            info!("eth_maxPriorityFeePerGas");
+            0u64.to_0x()
+        })?;
+        let helper = self.helper.clone();
+        module.register_method("eth_getCode", move |params, _| {
             info!("eth_getCode: {:#?}", params);
-            "0x1dfd14000"
+            let mut params = params.sequence();
+            let address: H160 = params.next::<String>().expect(INCORRECT_ARGS).from_0x();
+            let block: Option<BlockNumber> = params.optional_next().expect(INCORRECT_ARGS);
+
+            let helper = Self::lock(&helper);
+            let height = Self::state_height(&helper, block.as_ref());
+            let account = helper
+                .node
+                .account_info_at(&AccountId::Evm(address.into()), height);
+            let code: Vec<u8> = match account.and_then(|account| account.executable) {
+                Some(Executable::Evm()) => {
+                    let response = helper
+                        .node
+                        .system_view_at("evm_code".to_string(), &EvmAddress::from(address), height)
+                        .expect("Failed to view EVM code");
+                    borsh::from_slice(&response).unwrap()
+                }
+                Some(Executable::MultiVm(executable)) => multivm_code(&executable.image_id),
+                None => vec![],
+            };
+
+            let response = format!("0x{}", hex::encode(code));
+            info!("Response: {}", response);
+            response
+        })?;
+        let helper = self.helper.clone();
+        module.register_method("eth_getStorageAt", move |params, _| {
+            info!("eth_getStorageAt: {:#?}", params);
+            let mut params = params.sequence();
+            let address: H160 = params.next::<String>().expect(INCORRECT_ARGS).from_0x();
+            let position: String = params.next().expect(INCORRECT_ARGS);
+            let position =
+                U256::from_str_radix(position.trim_start_matches("0x"), 16).expect(INCORRECT_ARGS);
+            let block: Option<BlockNumber> = params.optional_next().expect(INCORRECT_ARGS);
+
+            let mut index = [0u8; 32];
+            position.to_big_endian(&mut index);
+
+            let helper = Self::lock(&helper);
+            let height = Self::state_height(&helper, block.as_ref());
+            let response = helper
+                .node
+                .system_view_at(
+                    "evm_storage".to_string(),
+                    &(EvmAddress::from(address), index),
+                    height,
+                )
+                .expect("Failed to view EVM storage");
+            let value: [u8; 32] = borsh::from_slice(&response).unwrap();
+
+            info!("Response: {}", H256::from(value).to_0x());
+            H256::from(value).to_0x()
         })?;
-        module.register_method("eth_estimateGas", |_, _| {
-            info!("eth_estimateGas");
-            "0x5208"
+        module.register_method("eth_estimateGas", |params, _| {
+            info!("eth_estimateGas: {:#?}", params.sequence());
+
+            let obj: HashMap<String, String> = params.sequence().next().unwrap_or_default();
+            let data = obj
+                .get("data")
+                .or_else(|| obj.get("input"))
+                .map(|data| data.trim_start_matches("0x"))
+                .unwrap_or_default();
+            if obj.contains_key("to") && data.is_empty() {
+                TRANSFER_GAS.to_0x()
+            } else {
+                CALL_GAS_ESTIMATE.to_0x()
+            }
         })?;
         let helper = self.helper.clone();
         module.register_method("eth_getTransactionCount", move |params, _| {
@@ -218,11 +371,14 @@ impl MultivmServer {
             for tx in helper.node.latest_block().txs {
                 if tx.hash().to_0x() == hash {
                     match tx {
-                        SupportedTransaction::MultiVm(_) => {
+                        SupportedTransaction::MultiVm(_) | SupportedTransaction::Delegated(_) => {
                             unreachable!("eth_getTransactionByHash for multiVM tx!")
                         }
                         SupportedTransaction::Evm(tx) => {
-                            let (tx_request, sig) = tx.decode();
+                            let Ok((tx_request, sig)) = tx.decode() else {
+                                error!("Failed to decode transaction {}", hash);
+                                return None;
+                            };
                             let result = Some(EthTransaction::from(
                                 tx_request,
                                 sig,
@@ -257,26 +413,34 @@ impl MultivmServer {
                 .from_0x();
             let data = hex::decode(data_str).expect(INCORRECT_ARGS);
 
-            let mut helper = Self::lock(&helper);
-            let node = &mut helper.node;
-
-            let rlp = ethers_core::utils::rlp::Rlp::new(&data);
-            let (tx, sig) =
-                ethers_core::types::TransactionRequest::decode_signed_rlp(&rlp).unwrap();
+            let tx = EthereumTransactionRequest::new(data);
+            let (tx_request, sig) = tx.decode().map_err(|error| {
+                error!("Invalid transaction {:#?}", error);
+                invalid_params(format!("Invalid transaction: {}", error))
+            })?;
+            let from = *tx_request
+                .from()
+                .ok_or_else(|| invalid_params("Transaction sender not recovered"))?;
 
-            match sig.verify(tx.sighash(), tx.from.unwrap()) {
-                Ok(_) => {}
-                Err(error) => info!("Invalid signature {:#?}", error),
+            if let Err(error) = sig.verify(tx_request.sighash(), from) {
+                error!("Invalid signature {:#?}", error);
+                return Err(invalid_params(format!("Invalid signature: {}", error)));
             }
 
-            let tx = SupportedTransaction::Evm(EthereumTransactionRequest::new(data));
+            let mut helper = Self::lock(&helper);
+            let node = &mut helper.node;
+
+            let tx = SupportedTransaction::Evm(tx);
             let hash = tx.hash();
-            node.add_tx(tx);
+            node.add_tx(tx).map_err(|error| {
+                error!("Transaction rejected {:#?}", error);
+                invalid_params(format!("Transaction rejected: {}", error))
+            })?;
             node.produce_block(true);
 
             info!("Response: {:#?}", hash.to_0x());
 
-            hash.to_0x()
+            Ok::<_, ErrorObjectOwned>(hash.to_0x())
         })?;
 
         let helper = self.helper.clone();
@@ -304,23 +468,81 @@ impl MultivmServer {
         })?;
 
         let helper = self.helper.clone();
-        module.register_method("mvm_deployContract", move |params, _| {
-            info!("mvm_deployContract");
+        module.register_method("mvm_sendTransaction", move |params, _| {
+            let params_str = format!("{:#?}", params);
+            info!(
+                "mvm_sendTransaction {:#?}",
+                if params_str.len() > 100 {
+                    "<params too long to log>"
+                } else {
+                    params_str.as_str()
+                }
+            );
+
+            let value: serde_json::Value = params
+                .sequence()
+                .next()
+                .map_err(|_| invalid_params(INCORRECT_ARGS))?;
+            let tx: SignedTransaction = parse_transaction(value)?;
+
+            let mut helper = Self::lock(&helper);
+            let node = &mut helper.node;
+
+            let tx = SupportedTransaction::MultiVm(tx);
+            let hash = tx.hash();
+            node.add_tx(tx).map_err(|error| {
+                error!("Transaction rejected {:#?}", error);
+                invalid_params(format!("Transaction rejected: {}", error))
+            })?;
+            node.produce_block(true);
+
+            info!("Response: {:#?}", hash.to_0x());
+
+            Ok::<_, ErrorObjectOwned>(hash.to_0x())
+        })?;
+
+        let helper = self.helper.clone();
+        module.register_method("mvm_sendDelegatedTransaction", move |params, _| {
+            info!("mvm_sendDelegatedTransaction");
 
-            let obj: HashMap<String, String> = params.sequence().next().expect(INCORRECT_ARGS);
-            let bytecode: Vec<u8> = obj.get("bytecode").expect(INCORRECT_ARGS).from_0x();
-            let multivm_name = obj.get("multivm").expect(INCORRECT_ARGS);
-            let private_key: String = obj.get("private_key").expect(INCORRECT_ARGS).from_0x();
-            let sk = SigningKey::from_slice(&hex::decode(private_key).expect(INCORRECT_ARGS))
-                .expect(INCORRECT_ARGS);
-            let account_id =
-                MultiVmAccountId::try_from(multivm_name.to_string()).expect(INCORRECT_ARGS);
+            // User signed transaction wrapped and signed by the relayer paying the fees
+            let value: serde_json::Value = params
+                .sequence()
+                .next()
+                .map_err(|_| invalid_params(INCORRECT_ARGS))?;
+            let tx: DelegatedTransaction = parse_transaction(value)?;
 
             let mut helper = Self::lock(&helper);
-            helper.deploy_contract_with_key(&account_id, bytecode, sk);
-            helper.produce_block(true);
+            let node = &mut helper.node;
+
+            let tx = SupportedTransaction::Delegated(tx);
+            let hash = tx.hash();
+            node.add_tx(tx).map_err(|error| {
+                error!("Transaction rejected {:#?}", error);
+                invalid_params(format!("Transaction rejected: {}", error))
+            })?;
+            node.produce_block(true);
+
+            info!("Response: {:#?}", hash.to_0x());
+
+            Ok::<_, ErrorObjectOwned>(hash.to_0x())
+        })?;
 
-            Some("0x0")
+        module.register_method("mvm_imageId", move |params, _| {
+            info!("mvm_imageId");
+
+            // Image ID is signed as a part of deployment transaction, clients may not compute it
+            let code: String = params
+                .sequence()
+                .next()
+                .map_err(|_| invalid_params(INCORRECT_ARGS))?;
+            let code = hex::decode(code.trim_start_matches("0x"))
+                .map_err(|_| invalid_params(INCORRECT_ARGS))?;
+            let image_id = image_id(&code).map_err(invalid_params)?;
+
+            info!("Response: {:?}", image_id);
+
+            Ok::<_, ErrorObjectOwned>(image_id)
         })?;
 
         let helper = self.helper.clone();
@@ -382,6 +604,87 @@ impl MultivmServer {
             }
         })?;
 
+        let helper = self.helper.clone();
+        module.register_method("mvm_traceTransaction", move |params, _| {
+            let hash = params.sequence().next::<String>().expect(INCORRECT_ARGS);
+            info!("mvm_traceTransaction: {:#?}", hash);
+
+            let hash: Option<Digest> = hex::decode(hash.trim_start_matches("0x"))
+                .ok()
+                .and_then(|hash| hash.try_into().ok());
+
+            let helper = Self::lock(&helper);
+            let block = hash.and_then(|hash| helper.node.block_by_transaction(&hash));
+            let receipt = block
+                .as_ref()
+                .zip(hash)
+                .and_then(|(block, hash)| block.receipt(&hash));
+            if let Some(receipt) = receipt {
+                info!("Response: {:#?}", receipt.call_trace);
+                return json!(receipt.call_trace);
+            }
+            info!("Response: null");
+            json!(null)
+        })?;
+
+        let helper = self.helper.clone();
+        module.register_method("debug_traceTransaction", move |params, _| {
+            info!("debug_traceTransaction: {:#?}", params.sequence());
+
+            let mut seq = params.sequence();
+            let hash: String = seq.next().map_err(|_| invalid_params(INCORRECT_ARGS))?;
+            let options: Option<serde_json::Value> = seq.optional_next().unwrap_or_default();
+            let hash: Digest = hex::decode(hash.trim_start_matches("0x"))
+                .ok()
+                .and_then(|hash| hash.try_into().ok())
+                .ok_or_else(|| invalid_params(INCORRECT_ARGS))?;
+
+            let helper = Self::lock(&helper);
+            let trace = helper
+                .node
+                .evm_trace(&hash)
+                .ok_or_else(|| invalid_params("Trace not found for transaction"))?;
+
+            Ok::<_, ErrorObjectOwned>(format_trace(&trace, options.as_ref()))
+        })?;
+
+        let helper = self.helper.clone();
+        module.register_method("debug_traceCall", move |params, _| {
+            info!("debug_traceCall: {:#?}", params.sequence());
+
+            let mut seq = params.sequence();
+            let obj: HashMap<String, String> =
+                seq.next().map_err(|_| invalid_params(INCORRECT_ARGS))?;
+            // Calls are traced against the latest state
+            let _block: Option<serde_json::Value> = seq.optional_next().unwrap_or_default();
+            let options: Option<serde_json::Value> = seq.optional_next().unwrap_or_default();
+
+            let from: Option<H160> = obj.get("from").map(|from| from.from_0x());
+            let to: H160 = obj
+                .get("to")
+                .ok_or_else(|| invalid_params(INCORRECT_ARGS))?
+                .from_0x();
+            let data = obj.get("data").or_else(|| obj.get("input"));
+            let input = match data {
+                Some(data) => hex::decode(data.trim_start_matches("0x"))
+                    .map_err(|_| invalid_params(INCORRECT_ARGS))?,
+                None => vec![],
+            };
+            let view = SupportedView::Evm(EvmCall {
+                from: from.map(|f| f.0),
+                to: to.0,
+                input,
+            });
+
+            let helper = Self::lock(&helper);
+            let trace = helper
+                .node
+                .trace_view(view)
+                .ok_or_else(|| invalid_params("Call is not executed by the EVM"))?;
+
+            Ok::<_, ErrorObjectOwned>(format_trace(&trace, options.as_ref()))
+        })?;
+
         let helper = self.helper.clone();
         module.register_method("mvm_accountInfo", move |params, _| {
             info!("mvm_accountInfo, {:#?}", params.sequence());
@@ -402,6 +705,163 @@ impl MultivmServer {
             json!(account)
         })?;
 
+        let helper = self.helper.clone();
+        module.register_method("mvm_codeHistory", move |params, _| {
+            info!("mvm_codeHistory, {:#?}", params.sequence());
+
+            let mut seq = params.sequence();
+            let address_name: String = seq.next().expect(INCORRECT_ARGS);
+
+            let account_id = if let Ok(h160) = H160::from_str(address_name.as_str()) {
+                AccountId::Evm(EvmAddress::try_from(h160).unwrap())
+            } else {
+                AccountId::MultiVm(MultiVmAccountId::try_from(address_name.clone()).unwrap())
+            };
+
+            let helper = Self::lock(&helper);
+            let history = helper.node.code_history(&account_id);
+
+            json!(history)
+        })?;
+
+        let helper = self.helper.clone();
+        module.register_method("mvm_validators", move |params, _| {
+            info!("mvm_validators, {:#?}", params.sequence());
+
+            // Epoch snapshot if the epoch is given, the current set otherwise
+            let mut seq = params.sequence();
+            let epoch: Option<u64> = seq.optional_next().unwrap_or_default();
+
+            let helper = Self::lock(&helper);
+            match epoch {
+                Some(epoch) => json!(helper.node.epoch_validators(epoch)),
+                None => json!(helper.node.validators()),
+            }
+        })?;
+
+        let helper = self.helper.clone();
+        module.register_method("eth_getLogs", move |params, _| {
+            info!("eth_getLogs: {:#?}", params.sequence());
+
+            let filter: Filter = params.sequence().next().expect(INCORRECT_ARGS);
+            let helper = Self::lock(&helper);
+            let latest = helper.node.latest_block().height;
+            let (from, to) = match filter.get_block_hash() {
+                Some(hash) => match helper.node.block_by_hash(&hash.0) {
+                    Some(block) => (block.height, block.height),
+                    None => {
+                        info!("Response: block not found");
+                        return json!([]);
+                    }
+                },
+                None => (
+                    block_height(filter.block_option.get_from_block(), latest),
+                    block_height(filter.block_option.get_to_block(), latest),
+                ),
+            };
+
+            let logs = Self::logs(&helper, &filter, from, to);
+            info!("Response: {} logs", logs.len());
+            json!(logs)
+        })?;
+
+        let helper = self.helper.clone();
+        let filters = self.filters.clone();
+        module.register_method("eth_newFilter", move |params, _| {
+            info!("eth_newFilter: {:#?}", params.sequence());
+
+            let filter: Filter = params.sequence().next().expect(INCORRECT_ARGS);
+            let latest = Self::lock(&helper).node.latest_block().height;
+            let last_polled_height =
+                block_height(filter.block_option.get_from_block(), latest).saturating_sub(1);
+
+            let mut filters = Self::lock_filters(&filters);
+            filters.next_id += 1;
+            let id = filters.next_id;
+            filters.filters.insert(
+                id,
+                LogFilter {
+                    filter,
+                    last_polled_height,
+                },
+            );
+
+            info!("Response: {}", id.to_0x());
+            id.to_0x()
+        })?;
+
+        let helper = self.helper.clone();
+        let filters = self.filters.clone();
+        module.register_method("eth_getFilterChanges", move |params, _| {
+            info!("eth_getFilterChanges: {:#?}", params.sequence());
+
+            let id: u64 = params
+                .sequence()
+                .next::<String>()
+                .expect(INCORRECT_ARGS)
+                .from_0x();
+            let helper = Self::lock(&helper);
+            let mut filters = Self::lock_filters(&filters);
+            let Some(log_filter) = filters.filters.get_mut(&id) else {
+                error!("Filter {} not found", id);
+                return json!([]);
+            };
+
+            let latest = helper.node.latest_block().height;
+            let to = block_height(log_filter.filter.block_option.get_to_block(), latest);
+            let logs = Self::logs(
+                &helper,
+                &log_filter.filter,
+                log_filter.last_polled_height + 1,
+                to,
+            );
+            // Blocks above `toBlock` are never polled, the filter stays at its end
+            log_filter.last_polled_height = log_filter.last_polled_height.max(to.min(latest));
+
+            info!("Response: {} logs", logs.len());
+            json!(logs)
+        })?;
+
+        let helper = self.helper.clone();
+        let filters = self.filters.clone();
+        module.register_method("eth_getFilterLogs", move |params, _| {
+            info!("eth_getFilterLogs: {:#?}", params.sequence());
+
+            let id: u64 = params
+                .sequence()
+                .next::<String>()
+                .expect(INCORRECT_ARGS)
+                .from_0x();
+            let helper = Self::lock(&helper);
+            let filters = Self::lock_filters(&filters);
+            let Some(log_filter) = filters.filters.get(&id) else {
+                error!("Filter {} not found", id);
+                return json!([]);
+            };
+
+            let latest = helper.node.latest_block().height;
+            let from = block_height(log_filter.filter.block_option.get_from_block(), latest);
+            let to = block_height(log_filter.filter.block_option.get_to_block(), latest);
+            let logs = Self::logs(&helper, &log_filter.filter, from, to);
+
+            info!("Response: {} logs", logs.len());
+            json!(logs)
+        })?;
+
+        let filters = self.filters.clone();
+        module.register_method("eth_uninstallFilter", move |params, _| {
+            info!("eth_uninstallFilter: {:#?}", params.sequence());
+
+            let id: u64 = params
+                .sequence()
+                .next::<String>()
+                .expect(INCORRECT_ARGS)
+                .from_0x();
+            let removed = Self::lock_filters(&filters).filters.remove(&id).is_some();
+            info!("Response: {}", removed);
+            removed
+        })?;
+
         for method in METHODS.iter() {
             module.register_method(method, move |_, _| {
                 info!("{}", method);
@@ -434,7 +894,7 @@ static ref METHODS: Vec<&'static str> = vec![
     "eth_accounts",
     // "eth_blockNumber",
     // "eth_getBalance",
-    "eth_getStorageAt",
+    // "eth_getStorageAt",
     // "eth_getTransactionCount",
     "eth_getBlockTransactionCountByHash",
     "eth_getBlockTransactionCountByNumber",
@@ -455,12 +915,12 @@ static ref METHODS: Vec<&'static str> = vec![
     // "eth_getTransactionReceipt",
     "eth_getUncleByBlockHashAndIndex",
     "eth_getUncleByBlockNumberAndIndex",
-    "eth_newFilter",
+    // "eth_newFilter",
     "eth_newBlockFilter",
     "eth_newPendingTransactionFilter",
-    "eth_uninstallFilter",
-    "eth_getFilterChanges",
-    "eth_getFilterLogs",
-    "eth_getLogs",
+    // "eth_uninstallFilter",
+    // "eth_getFilterChanges",
+    // "eth_getFilterLogs",
+    // "eth_getLogs",
 ];
 }
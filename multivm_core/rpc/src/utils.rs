@@ -1,7 +1,13 @@
 use eth_primitive_types::{H160, H256, U256};
-use ethers_core::types::{Signature, TransactionRequest};
-use multivm_primitives::{Block, Digest, SupportedTransaction};
+use ethers_core::types::{
+    transaction::eip2718::TypedTransaction, BlockNumber, Filter, Signature, ValueOrArray,
+};
+use multivm_primitives::{
+    Block, Digest, EthereumTransactionRequest, EvmCallFrame, EvmLog, EvmStructLog, EvmTrace,
+    Receipt, ReceiptLog, SupportedTransaction, BASE_FEE_PER_GAS,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 pub trait To0x {
     fn to_0x(&self) -> String;
@@ -59,7 +65,7 @@ impl To0x for H256 {
 
 impl To0x for U256 {
     fn to_0x(&self) -> String {
-        format!("{:#032X}", self).to_string()
+        format!("{:#x}", self).to_string()
     }
 }
 
@@ -125,6 +131,7 @@ pub struct EthTransactionInput {
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct EthBlockOutput {
+    pub base_fee_per_gas: String,
     pub difficulty: String,
     pub extra_data: String,
     pub gas_limit: String,
@@ -150,12 +157,13 @@ pub struct EthBlockOutput {
 impl EthBlockOutput {
     pub fn from(block: &Block) -> Self {
         Self {
+            base_fee_per_gas: BASE_FEE_PER_GAS.to_0x(),
             difficulty: String::default_zero(),
             extra_data: String::default_hash(),
             gas_limit: String::default_zero(),
             gas_used: String::default_zero(),
             hash: block.hash.to_0x(),
-            logs_bloom: format!("0x{}", (0..512).map(|_| "0").collect::<String>()),
+            logs_bloom: block.logs_bloom.to_0x(),
             miner: String::default_address(),
             mix_hash: String::default_hash(),
             nonce: "0x0000000000000000".to_string(),
@@ -191,30 +199,77 @@ pub struct EthTransaction {
     pub v: String,
     pub r: String,
     pub s: String,
+    #[serde(rename = "type")]
+    pub tx_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<String>,
 }
 
 impl EthTransaction {
-    pub fn from(tx: TransactionRequest, sig: Signature, hash: String, block: Block) -> Self {
+    pub fn from(tx: TypedTransaction, sig: Signature, hash: String, block: Block) -> Self {
+        let (max_fee_per_gas, max_priority_fee_per_gas) = match &tx {
+            TypedTransaction::Eip1559(tx) => (
+                tx.max_fee_per_gas.map(|fee| fee.to_0x()),
+                tx.max_priority_fee_per_gas.map(|fee| fee.to_0x()),
+            ),
+            _ => (None, None),
+        };
+
         Self {
             block_hash: block.hash.to_0x(),
             block_number: block.height.to_0x(),
-            from: tx.from.map(|x| x.to_0x()),
-            gas: tx.gas.unwrap().to_string().to_0x(),
-            gas_price: tx.gas_price.unwrap().to_string().to_0x(),
+            from: tx.from().map(|x| x.to_0x()),
+            gas: tx.gas().cloned().unwrap_or_default().to_0x(),
+            gas_price: effective_gas_price(&tx).to_0x(),
             hash,
-            // input: tx.data.map(|x| x.to_vec().to_0x()),
-            input: Some("0x68656c6c6f21".to_string()),
-            nonce: tx.nonce.unwrap().to_string(),
-            to: tx.to.map(|x| x.as_address().unwrap().to_0x()),
+            input: tx.data().map(|x| x.to_vec().to_0x()),
+            nonce: tx.nonce().cloned().unwrap_or_default().to_0x(),
+            to: tx.to_addr().map(|x| x.to_0x()),
             transaction_index: "0x1".to_string(),
-            value: tx.value.unwrap().to_0x(),
+            value: tx.value().cloned().unwrap_or_default().to_0x(),
             v: sig.v.to_0x(),
             r: sig.r.to_0x(),
             s: sig.s.to_0x(),
+            tx_type: tx_type(&tx).to_0x(),
+            chain_id: tx.chain_id().map(|chain_id| chain_id.as_u64().to_0x()),
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
         }
     }
 }
 
+/// Returns EIP-2718 transaction type
+pub fn tx_type(tx: &TypedTransaction) -> u64 {
+    match tx {
+        TypedTransaction::Eip2930(_) => 1,
+        TypedTransaction::Eip1559(_) => 2,
+        _ => 0,
+    }
+}
+
+/// Returns gas price paid by the transaction under the current base fee
+pub fn effective_gas_price(tx: &TypedTransaction) -> u128 {
+    EthereumTransactionRequest::effective_gas_price(tx, BASE_FEE_PER_GAS).unwrap_or_default()
+}
+
+/// Returns the address of the contract created by the deploy transaction,
+/// system contract responds to EVM deployments with `(address, output)`,
+/// the address is zero when the creation failed
+fn created_contract_address(tx_hash: &Digest, block: &Block) -> Option<String> {
+    let output = match block.call_outputs.get(tx_hash)? {
+        Ok(output) => output,
+        Err(_) => return None,
+    };
+    let (address, _): ([u8; 20], Vec<u8>) = borsh::from_slice(output).ok()?;
+    let address = H160::from(address);
+
+    (!address.is_zero()).then(|| address.to_0x())
+}
+
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct EthTransactionReceipt {
@@ -225,7 +280,7 @@ pub struct EthTransactionReceipt {
     pub effective_gas_price: String,
     pub from: String,
     pub gas_used: String,
-    pub logs: Vec<String>,
+    pub logs: Vec<EthLog>,
     pub logs_bloom: String,
     pub status: String,
     pub to: String,
@@ -237,23 +292,341 @@ pub struct EthTransactionReceipt {
 
 impl EthTransactionReceipt {
     pub fn from(tx: &SupportedTransaction, hash: String, block: &Block) -> Self {
-        let from = "0x69288587af88e2c6f43832F482334a01F30e2F01";
-        let to = "0x06A85356DCb5b307096726FB86A78c59D38e08ee";
+        let mut from = "0x69288587af88e2c6f43832F482334a01F30e2F01".to_string();
+        let mut to = "0x06A85356DCb5b307096726FB86A78c59D38e08ee".to_string();
+        let mut gas_price = 0;
+        let mut transaction_type = 0;
+        let mut contract_address = None;
+        let tx_hash = tx.hash();
+        if let SupportedTransaction::Evm(tx) = tx {
+            if let Ok((tx, _sig)) = tx.decode() {
+                if tx.to().is_none() {
+                    contract_address = created_contract_address(&tx_hash, block);
+                }
+                from = tx.from().map(|x| x.to_0x()).unwrap_or(from);
+                to = tx.to_addr().map(|x| x.to_0x()).unwrap_or(to);
+                gas_price = effective_gas_price(&tx);
+                transaction_type = tx_type(&tx);
+            }
+        }
+        let receipt = block
+            .receipt(&tx_hash)
+            .expect("Receipt not found for included transaction");
         Self {
             block_hash: block.hash.to_0x(),
             block_number: block.height.to_0x(),
-            contract_address: None,
+            contract_address,
             cumulative_gas_used: "0x0".to_string(),
-            effective_gas_price: "0x0".to_string(),
-            from: from.to_string(),
+            effective_gas_price: gas_price.to_0x(),
+            from,
             gas_used: "0x0".to_string(),
-            logs: vec![],
-            logs_bloom: format!("0x{}", (0..128).map(|_| "0").collect::<String>()),
-            status: "0x1".to_string(),
-            to: to.to_string(),
+            logs: receipt
+                .logs
+                .iter()
+                .map(|log| EthLog::from(log, receipt, block))
+                .collect(),
+            logs_bloom: receipt.logs_bloom.to_0x(),
+            status: (receipt.status as u64).to_0x(),
+            to,
             transaction_hash: hash.to_0x(),
-            transaction_index: "0x0".to_string(),
-            tx_type: "0x2".to_string(),
+            transaction_index: receipt.transaction_index.to_0x(),
+            tx_type: transaction_type.to_0x(),
         }
     }
 }
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EthLog {
+    pub address: String,
+    pub topics: Vec<String>,
+    pub data: String,
+    pub block_hash: String,
+    pub block_number: String,
+    pub transaction_hash: String,
+    pub transaction_index: String,
+    pub log_index: String,
+    pub removed: bool,
+}
+
+impl EthLog {
+    pub fn from(log: &ReceiptLog, receipt: &Receipt, block: &Block) -> Self {
+        Self {
+            address: H160::from(log.log.address.clone()).to_0x(),
+            topics: log.log.topics.iter().map(|topic| topic.to_0x()).collect(),
+            data: log.log.data.to_0x(),
+            block_hash: block.hash.to_0x(),
+            block_number: block.height.to_0x(),
+            transaction_hash: receipt.transaction_hash.to_0x(),
+            transaction_index: receipt.transaction_index.to_0x(),
+            log_index: log.log_index.to_0x(),
+            removed: false,
+        }
+    }
+}
+
+/// Resolves filter block tag into block height
+pub fn block_height(block: Option<&BlockNumber>, latest: u64) -> u64 {
+    match block {
+        Some(BlockNumber::Number(number)) => number.as_u64(),
+        Some(BlockNumber::Earliest) => 0,
+        _ => latest,
+    }
+}
+
+/// Code reported for MultiVM contracts: INVALID opcode, `multivm` and the image ID
+pub fn multivm_code(image_id: &[u32; 8]) -> Vec<u8> {
+    let mut code = vec![0xfe];
+    code.extend(b"multivm");
+    image_id
+        .iter()
+        .for_each(|word| code.extend(word.to_le_bytes()));
+    code
+}
+
+/// Returns true if log satisfies filter address and topics
+pub fn filter_matches(filter: &Filter, log: &EvmLog) -> bool {
+    let address: H160 = log.address.clone().into();
+    let address_matches = match &filter.address {
+        None => true,
+        Some(ValueOrArray::Value(expected)) => *expected == address,
+        Some(ValueOrArray::Array(expected)) => expected.is_empty() || expected.contains(&address),
+    };
+
+    let topics_match = filter.topics.iter().enumerate().all(|(index, topic)| {
+        let expected = match topic {
+            None | Some(ValueOrArray::Value(None)) => return true,
+            Some(ValueOrArray::Array(topics)) if topics.contains(&None) => return true,
+            Some(ValueOrArray::Value(Some(topic))) => vec![*topic],
+            Some(ValueOrArray::Array(topics)) => topics.iter().flatten().cloned().collect(),
+        };
+
+        expected.is_empty()
+            || log
+                .topics
+                .get(index)
+                .is_some_and(|topic| expected.contains(&H256::from(*topic)))
+    });
+
+    address_matches && topics_match
+}
+
+/// Formats EVM trace as geth `callTracer` output if requested, `structLogger` otherwise
+pub fn format_trace(trace: &EvmTrace, options: Option<&Value>) -> Value {
+    let tracer = options
+        .and_then(|options| options.get("tracer"))
+        .and_then(Value::as_str);
+
+    match tracer {
+        Some("callTracer") => json!(EthCallFrame::from(&trace.call)),
+        _ => json!(EthStructLogger::from(trace)),
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EthCallFrame {
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub gas: String,
+    pub gas_used: String,
+    pub input: String,
+    pub output: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<EthCallFrame>,
+}
+
+impl EthCallFrame {
+    pub fn from(frame: &EvmCallFrame) -> Self {
+        let revert_reason = frame
+            .error
+            .as_ref()
+            .and_then(|_| revert_reason(&frame.output));
+
+        Self {
+            call_type: frame.call_type.clone(),
+            from: H160::from(frame.from).to_0x(),
+            to: H160::from(frame.to).to_0x(),
+            value: U256::from_big_endian(&frame.value).to_0x(),
+            gas: frame.gas.to_0x(),
+            gas_used: frame.gas_used.to_0x(),
+            input: frame.input.to_0x(),
+            output: frame.output.to_0x(),
+            error: frame.error.clone(),
+            revert_reason,
+            calls: frame.calls.iter().map(EthCallFrame::from).collect(),
+        }
+    }
+}
+
+/// Decodes `Error(string)` revert data
+fn revert_reason(output: &[u8]) -> Option<String> {
+    let data = output.strip_prefix(&[0x08, 0xc3, 0x79, 0xa0])?;
+    let tokens = ethers_core::abi::decode(&[ethers_core::abi::ParamType::String], data).ok()?;
+    tokens.into_iter().next()?.into_string()
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EthStructLogger {
+    pub gas: u64,
+    pub failed: bool,
+    pub return_value: String,
+    pub struct_logs: Vec<EthStructLog>,
+}
+
+impl EthStructLogger {
+    pub fn from(trace: &EvmTrace) -> Self {
+        Self {
+            gas: trace.gas_used,
+            failed: trace.failed,
+            return_value: hex::encode(&trace.return_value),
+            struct_logs: trace.struct_logs.iter().map(EthStructLog::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EthStructLog {
+    pub pc: u64,
+    pub op: String,
+    pub gas: u64,
+    pub gas_cost: u64,
+    pub depth: u64,
+    pub stack: Vec<String>,
+}
+
+impl EthStructLog {
+    pub fn from(log: &EvmStructLog) -> Self {
+        Self {
+            pc: log.pc,
+            op: log.op.clone(),
+            gas: log.gas,
+            gas_cost: log.gas_cost,
+            depth: log.depth,
+            stack: log
+                .stack
+                .iter()
+                .map(|word| U256::from_big_endian(word).to_0x())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::abi::ethereum_types::{Bloom, BloomInput};
+    use multivm_primitives::{logs_bloom, ContractError};
+
+    use super::*;
+
+    fn log(address: u8, topics: &[u8]) -> EvmLog {
+        EvmLog {
+            address: H160::repeat_byte(address).into(),
+            topics: topics.iter().map(|topic| [*topic; 32]).collect(),
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn test_logs_bloom() {
+        assert_eq!(logs_bloom(&[]), Bloom::zero().as_bytes());
+
+        let bloom = Bloom::from_slice(&logs_bloom(&[log(1, &[2, 3])]));
+        assert!(bloom.contains_input(BloomInput::Raw(&[1; 20])));
+        assert!(bloom.contains_input(BloomInput::Raw(&[2; 32])));
+        assert!(bloom.contains_input(BloomInput::Raw(&[3; 32])));
+        assert!(!bloom.contains_input(BloomInput::Raw(&[4; 32])));
+    }
+
+    #[test]
+    fn test_filter_matches_address() {
+        let log = log(1, &[2]);
+
+        assert!(filter_matches(&Filter::new(), &log));
+        assert!(filter_matches(
+            &Filter::new().address(H160::repeat_byte(1)),
+            &log
+        ));
+        assert!(!filter_matches(
+            &Filter::new().address(H160::repeat_byte(2)),
+            &log
+        ));
+        assert!(filter_matches(
+            &Filter::new().address(vec![H160::repeat_byte(2), H160::repeat_byte(1)]),
+            &log
+        ));
+        assert!(filter_matches(&Filter::new().address(vec![]), &log));
+    }
+
+    #[test]
+    fn test_filter_matches_topics() {
+        let log = log(1, &[2, 3]);
+
+        assert!(filter_matches(
+            &Filter::new().topic0(H256::repeat_byte(2)),
+            &log
+        ));
+        assert!(!filter_matches(
+            &Filter::new().topic0(H256::repeat_byte(3)),
+            &log
+        ));
+        // Topics are positional, any of the alternatives matches
+        assert!(filter_matches(
+            &Filter::new().topic1(vec![H256::repeat_byte(4), H256::repeat_byte(3)]),
+            &log
+        ));
+        // Wildcard position matches anything, a missing topic matches nothing
+        assert!(filter_matches(
+            &Filter::new()
+                .topic0(ValueOrArray::<Option<H256>>::Value(None))
+                .topic1(H256::repeat_byte(3)),
+            &log
+        ));
+        assert!(!filter_matches(
+            &Filter::new().topic2(H256::repeat_byte(3)),
+            &log
+        ));
+    }
+
+    #[test]
+    fn test_created_contract_address() {
+        let created = ([1u8; 20], vec![]);
+        let failed = ([0u8; 20], vec![]);
+        let block = Block {
+            height: 1,
+            hash: [0; 32],
+            parent_hash: [0; 32],
+            previous_global_root: Default::default(),
+            new_global_root: Default::default(),
+            timestamp: 0,
+            txs: Default::default(),
+            call_outputs: [
+                ([1; 32], Ok(borsh::to_vec(&created).unwrap())),
+                ([2; 32], Ok(borsh::to_vec(&failed).unwrap())),
+                ([3; 32], Err(ContractError::new("Out of gas".to_string()))),
+            ]
+            .into_iter()
+            .collect(),
+            receipts: Default::default(),
+            logs_bloom: logs_bloom(&[]),
+            producer: None,
+        };
+
+        assert_eq!(
+            created_contract_address(&[1; 32], &block),
+            Some(H160::repeat_byte(1).to_0x())
+        );
+        assert_eq!(created_contract_address(&[2; 32], &block), None);
+        assert_eq!(created_contract_address(&[3; 32], &block), None);
+        assert_eq!(created_contract_address(&[4; 32], &block), None);
+    }
+}
@@ -27,4 +27,6 @@ pub struct Account {
     pub executable: Option<Executable>,
     pub balance: u128,
     pub nonce: u64,
+    /// Bytes of contract storage, lock `STORAGE_PRICE_PER_BYTE` of the balance each
+    pub storage_usage: u64,
 }
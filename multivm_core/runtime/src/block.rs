@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
-use multivm_primitives::{Block, Commitment, Digest, SupportedTransaction};
+use multivm_primitives::{
+    logs_bloom, AccountId, Block, Commitment, Digest, Receipt, ReceiptLog, SupportedTransaction,
+};
 use tracing::info;
 
 use crate::outcome::ExecutionOutcome;
@@ -14,6 +16,7 @@ pub struct UnprovedBlock {
     pub timestamp: u64,
     pub txs: Vec<SupportedTransaction>,
     pub execution_outcomes: HashMap<Digest, ExecutionOutcome>,
+    pub producer: Option<AccountId>,
 }
 
 impl UnprovedBlock {
@@ -34,6 +37,9 @@ impl UnprovedBlock {
                                 SupportedTransaction::MultiVm(multivm_tx) => {
                                     multivm_tx.transaction.calls.iter().map(|call| call.method.clone()).collect::<Vec<_>>()
                                 }
+                                SupportedTransaction::Delegated(delegated_tx) => {
+                                    delegated_tx.transaction.transaction.calls.iter().map(|call| call.method.clone()).collect::<Vec<_>>()
+                                }
                                 // TODO: replace with proper method
                                 SupportedTransaction::Evm(_) => {vec!["evm call".to_string()]}
                             };
@@ -70,6 +76,45 @@ impl UnprovedBlock {
             })
             .collect();
 
+        let mut log_index = 0;
+        let receipts: Vec<_> = self
+            .txs
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                let hash = tx.hash();
+                let status = call_outputs
+                    .get(&hash)
+                    .map(|response| response.is_ok())
+                    .unwrap_or_default();
+                let logs = self
+                    .execution_outcomes
+                    .get(&hash)
+                    .map(|outcome| outcome.logs())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|log| {
+                        let log = ReceiptLog { log_index, log };
+                        log_index += 1;
+                        log
+                    })
+                    .collect();
+
+                let call_trace = self
+                    .execution_outcomes
+                    .get(&hash)
+                    .map(|outcome| outcome.call_trace.clone());
+
+                Receipt::new(hash, index as u64, status, logs, call_trace)
+            })
+            .collect();
+
+        let logs_bloom = logs_bloom(
+            receipts
+                .iter()
+                .flat_map(|receipt| receipt.logs.iter().map(|log| &log.log)),
+        );
+
         Block {
             height: self.height,
             hash: self.hash,
@@ -79,6 +124,9 @@ impl UnprovedBlock {
             timestamp: self.timestamp,
             txs: self.txs,
             call_outputs,
+            receipts,
+            logs_bloom,
+            producer: self.producer,
         }
     }
 }
@@ -4,17 +4,21 @@ use risc0_zkvm::sha::{Impl as HashImpl, Sha256};
 use tracing::{debug, span, Level};
 
 use multivm_primitives::{
+    image_id_hex,
     syscalls::{
-        CrossContractCallRequest, DeployContractRequest, GetStorageResponse, SetStorageRequest,
-        CROSS_CONTRACT_CALL, DEPLOY_CONTRACT_CALL, GET_STORAGE_CALL, SET_STORAGE_CALL,
+        CrossContractCallRequest, DeleteCodeRequest, DeleteStorageRequest, DeployContractRequest,
+        GetStorageResponse, SetStorageRequest, UploadCodeRequest, CALL_CYCLES_CALL,
+        CROSS_CONTRACT_CALL, DELETE_CODE_CALL, DELETE_STORAGE_CALL, DEPLOY_CONTRACT_CALL,
+        EVM_TRACE_CALL, GET_STORAGE_CALL, SET_STORAGE_CALL, UPLOAD_CODE_CALL,
     },
     AccountId, Attachments, ContractCallContext, EnvironmentContext, SupportedTransaction,
 };
 
 use crate::{
+    code_store,
     executor::{ContractLogger, Executor},
-    outcome::ExecutionOutcome,
-    utils,
+    outcome::{CallInfo, CallRecord, ExecutionOutcome},
+    storage, utils,
     viewer::SupportedView,
 };
 
@@ -29,6 +33,8 @@ pub enum Action {
     View(SupportedView, EnvironmentContext),
     Call(ContractCallContext),
     EvmCall(ContractCallContext),
+    TraceTransaction(SupportedTransaction, EnvironmentContext),
+    TraceView(SupportedView, EnvironmentContext),
 }
 
 pub struct Bootstraper {
@@ -37,7 +43,13 @@ pub struct Bootstraper {
     signer_id: AccountId,
     attachments: Option<Attachments>,
     cross_calls_outcomes: Rc<RefCell<Vec<ExecutionOutcome>>>,
+    record: Rc<RefCell<CallRecord>>,
+    /// Previous images of the contracts deployed during the transaction
+    replaced_code: RefCell<Vec<(AccountId, Option<[u32; 8]>)>>,
+    /// Images released during the transaction, removed once it succeeds
+    deleted_code: RefCell<Vec<[u32; 8]>>,
     environment: EnvironmentContext,
+    trace: bool,
 }
 
 impl Bootstraper {
@@ -50,6 +62,9 @@ impl Bootstraper {
         let attachments = match &transaction {
             SupportedTransaction::MultiVm(multivm_tx) => Some(multivm_tx.attachments.clone()),
             SupportedTransaction::Evm(_) => None,
+            SupportedTransaction::Delegated(delegated_tx) => {
+                Some(delegated_tx.transaction.attachments.clone())
+            }
         }
         .flatten();
 
@@ -59,17 +74,32 @@ impl Bootstraper {
             signer_id,
             attachments,
             cross_calls_outcomes: Default::default(),
+            record: Default::default(),
+            replaced_code: Default::default(),
+            deleted_code: Default::default(),
             environment,
+            trace: false,
         }
     }
 
+    /// Enables EVM execution tracing, traces are stored by transaction hash.
+    /// Should be used in non-proving mode only
+    pub fn with_trace(mut self) -> Self {
+        self.trace = true;
+        self
+    }
+
     pub fn bootstrap(self) -> ExecutionOutcome {
         debug!(
             // tx_hash = utils::bytes_to_hex(self.signed_tx.transaction.hash().as_slice()),
             "Bootstraping transaction"
         );
 
-        let action = Action::ExecuteTransaction(self.transaction.clone(), self.environment.clone());
+        let action = if self.trace {
+            Action::TraceTransaction(self.transaction.clone(), self.environment.clone())
+        } else {
+            Action::ExecuteTransaction(self.transaction.clone(), self.environment.clone())
+        };
 
         let action_bytes = borsh::to_vec(&action).unwrap();
 
@@ -79,8 +109,16 @@ impl Bootstraper {
             .io_callback(CROSS_CONTRACT_CALL, self.callback_on_cross_contract_call())
             .io_callback(GET_STORAGE_CALL, self.callback_on_get_storage())
             .io_callback(SET_STORAGE_CALL, self.callback_on_set_storage())
+            .io_callback(DELETE_STORAGE_CALL, self.callback_on_delete_storage())
+            .io_callback(UPLOAD_CODE_CALL, self.callback_on_code_upload())
+            .io_callback(DELETE_CODE_CALL, self.callback_on_code_deletion())
             .io_callback(DEPLOY_CONTRACT_CALL, self.callback_on_contract_deployment())
-            .stdout(ContractLogger::new(AccountId::system_meta_contract()))
+            .io_callback(EVM_TRACE_CALL, self.callback_on_evm_trace())
+            .io_callback(CALL_CYCLES_CALL, self.callback_on_call_cycles())
+            .stdout(ContractLogger::new(
+                AccountId::system_meta_contract(),
+                self.record.clone(),
+            ))
             .build()
             .unwrap();
 
@@ -92,7 +130,122 @@ impl Bootstraper {
 
         let session = exec.execute(env, image).unwrap();
 
-        ExecutionOutcome::new(session, 0, self.cross_calls_outcomes.take())
+        let outcome = ExecutionOutcome::new(
+            session,
+            0,
+            self.cross_calls_outcomes.take(),
+            self.call_info(),
+            self.record.take(),
+        );
+
+        // Contract images are switched in the db right away, failed transaction mustn't keep them
+        if outcome.commitment.response.is_err() {
+            code_store::revert_contract_code(&self.db, self.replaced_code.take());
+        } else {
+            for image_id in self.deleted_code.take() {
+                debug!(image_id = image_id_hex(&image_id), "Removing unused code");
+                code_store::remove(&self.db, &image_id);
+            }
+        }
+
+        outcome
+    }
+
+    /// Transaction is the root of the call tree, executed by the system meta-contract
+    fn call_info(&self) -> CallInfo {
+        let (method, gas) = match &self.transaction {
+            SupportedTransaction::MultiVm(tx) => (
+                "transaction".to_string(),
+                tx.transaction
+                    .calls
+                    .iter()
+                    .map(|call| call.gas)
+                    .sum::<u64>(),
+            ),
+            SupportedTransaction::Delegated(tx) => (
+                "delegated_transaction".to_string(),
+                tx.transaction
+                    .transaction
+                    .calls
+                    .iter()
+                    .map(|call| call.gas)
+                    .sum::<u64>(),
+            ),
+            SupportedTransaction::Evm(tx) => (
+                "evm_transaction".to_string(),
+                tx.decode()
+                    .ok()
+                    .and_then(|(tx, _)| tx.gas().map(|gas| gas.as_u64()))
+                    .unwrap_or_default(),
+            ),
+        };
+
+        CallInfo {
+            contract_id: AccountId::system_meta_contract(),
+            method,
+            args_hash: self.transaction.hash(),
+            caller: self.signer_id.clone(),
+            gas,
+        }
+    }
+
+    /// Verifies and stores the image attached to the transaction in the code store
+    fn store_attached_code(&self, image_id: &[u32; 8]) {
+        let image_bytes = self
+            .attachments
+            .as_ref()
+            .map(|attachments| attachments.contracts_images.get(image_id))
+            .flatten();
+
+        // TODO: error handling
+        let Some(image_bytes) = image_bytes else {
+            panic!("Contract image not found");
+        };
+
+        let program = risc0_zkvm::Program::load_elf(&image_bytes, MAX_MEMORY).unwrap();
+        let image = risc0_zkvm::MemoryImage::new(&program, PAGE_SIZE).unwrap();
+
+        // TODO: error handling
+        if image.compute_id() != (*image_id).into() {
+            panic!("Invalid image id");
+        }
+
+        code_store::insert(&self.db, image_id, image_bytes.clone());
+    }
+
+    pub fn callback_on_code_upload<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |from_guest| {
+            let span = span!(Level::DEBUG, "upload_code call handler");
+            let _enter = span.enter();
+
+            let request: UploadCodeRequest = BorshDeserialize::try_from_slice(&from_guest).unwrap();
+
+            self.store_attached_code(&request.image_id);
+            // Uploaded again after it was released, e.g. by another call of the transaction
+            self.deleted_code
+                .borrow_mut()
+                .retain(|image_id| *image_id != request.image_id);
+
+            Ok(Default::default())
+        }
+    }
+
+    pub fn callback_on_code_deletion<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |from_guest| {
+            let span = span!(Level::DEBUG, "delete_code call handler");
+            let _enter = span.enter();
+
+            let request: DeleteCodeRequest = BorshDeserialize::try_from_slice(&from_guest).unwrap();
+
+            // Failed transaction keeps the code, it is removed after the execution
+            self.deleted_code.borrow_mut().push(request.image_id);
+
+            Ok(Default::default())
+        }
     }
 
     pub fn callback_on_contract_deployment<'a>(
@@ -105,35 +258,53 @@ impl Bootstraper {
             let request: DeployContractRequest =
                 BorshDeserialize::try_from_slice(&from_guest).unwrap();
 
-            let image_bytes = self
-                .attachments
-                .as_ref()
-                .map(|attachments| attachments.contracts_images.get(&request.image_id))
-                .flatten();
-
             // TODO: error handling
-            let Some(image_bytes) = image_bytes else {
-                panic!("Contract image not found");
-            };
+            if code_store::get(&self.db, &request.image_id).is_none() {
+                panic!("Contract image is not uploaded");
+            }
 
-            let program = risc0_zkvm::Program::load_elf(&image_bytes, MAX_MEMORY).unwrap();
-            let image = risc0_zkvm::MemoryImage::new(&program, PAGE_SIZE).unwrap();
+            self.replaced_code.borrow_mut().push((
+                request.account_id.clone(),
+                code_store::contract_image_id(&self.db, &request.account_id),
+            ));
+            code_store::set_contract_code(&self.db, &request.account_id, &request.image_id);
 
-            // TODO: error handling
-            if image.compute_id() != request.image_id.into() {
-                panic!("Invalid image id");
-            }
+            Ok(Default::default())
+        }
+    }
 
-            let db_key = format!("contracts_code.{}", request.account_id.to_string());
+    pub fn callback_on_evm_trace<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |from_guest| {
+            let db_key = format!(
+                "evm_traces.{}",
+                utils::bytes_to_hex(&self.transaction.hash())
+            );
 
             self.db
-                .insert(db_key, image_bytes.clone())
-                .expect("Failed to insert image to db");
+                .insert(db_key, from_guest.to_vec())
+                .expect("Failed to insert EVM trace to db");
 
             Ok(Default::default())
         }
     }
 
+    pub fn callback_on_call_cycles<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |_from_guest| {
+            let cycles = self
+                .cross_calls_outcomes
+                .borrow()
+                .last()
+                .map(ExecutionOutcome::total_cycles)
+                .unwrap_or_default();
+
+            Ok(borsh::to_vec(&cycles).unwrap().into())
+        }
+    }
+
     pub fn callback_on_cross_contract_call<'a>(
         &'a self,
     ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
@@ -196,6 +367,8 @@ impl Bootstraper {
 
             debug!(contract=?AccountId::system_meta_contract(), key=?key, "Loading storage");
 
+            self.record.borrow_mut().storage_reads.push(key);
+
             Ok(response_bytes.into())
         }
     }
@@ -214,15 +387,40 @@ impl Bootstraper {
 
             debug!(contract=?AccountId::system_meta_contract(), key=?request.key, new_hash = utils::bytes_to_hex(hash2.as_bytes()), "Updating storage");
 
-            let db_key = format!(
-                "committed_storage.{}.{}",
-                AccountId::system_meta_contract(),
-                request.key
+            storage::insert(
+                &self.db,
+                &AccountId::system_meta_contract(),
+                &request.key,
+                self.environment.block_height,
+                request.storage,
             );
 
-            self.db
-                .insert(db_key, request.storage)
-                .expect("Failed to insert storage to db");
+            self.record.borrow_mut().storage_writes.push(request.key);
+
+            Ok(Default::default())
+        }
+    }
+
+    pub fn callback_on_delete_storage<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |from_guest| {
+            let span = span!(Level::DEBUG, "delete_storage call handler");
+            let _enter = span.enter();
+
+            let request: DeleteStorageRequest =
+                BorshDeserialize::try_from_slice(&from_guest).unwrap();
+
+            debug!(contract=?AccountId::system_meta_contract(), key=?request.key, "Deleting storage");
+
+            storage::remove(
+                &self.db,
+                &AccountId::system_meta_contract(),
+                &request.key,
+                self.environment.block_height,
+            );
+
+            self.record.borrow_mut().storage_writes.push(request.key);
 
             Ok(Default::default())
         }
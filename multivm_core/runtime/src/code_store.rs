@@ -0,0 +1,88 @@
+use multivm_primitives::{image_id_hex, AccountId};
+use tracing::info;
+
+const MAX_MEMORY: u32 = 0x10000000;
+const PAGE_SIZE: u32 = 0x400;
+
+fn code_key(image_id: &[u32; 8]) -> String {
+    format!("code_store.{}", image_id_hex(image_id))
+}
+
+fn contract_key(account_id: &AccountId) -> String {
+    format!("contracts_code.{}", account_id)
+}
+
+/// Returns the image ELF from the code store
+pub fn get(db: &sled::Db, image_id: &[u32; 8]) -> Option<Vec<u8>> {
+    db.get(code_key(image_id))
+        .expect("Failed to get code from db")
+        .map(|v| v.to_vec())
+}
+
+pub fn insert(db: &sled::Db, image_id: &[u32; 8], code: Vec<u8>) {
+    db.insert(code_key(image_id), code)
+        .expect("Failed to insert code to db");
+}
+
+pub fn remove(db: &sled::Db, image_id: &[u32; 8]) {
+    db.remove(code_key(image_id))
+        .expect("Failed to remove code from db");
+}
+
+/// Returns the image ID the contract is deployed from
+pub fn contract_image_id(db: &sled::Db, account_id: &AccountId) -> Option<[u32; 8]> {
+    let image_id = db
+        .get(contract_key(account_id))
+        .expect("Failed to get contract code from db")?;
+
+    Some(borsh::from_slice(&image_id).expect("Corrupted contract image id"))
+}
+
+/// Returns the image ELF the contract is deployed from
+pub fn contract_code(db: &sled::Db, account_id: &AccountId) -> Option<Vec<u8>> {
+    get(db, &contract_image_id(db, account_id)?)
+}
+
+/// Points the contract to the image, contracts deployed from the same image share its code
+pub fn set_contract_code(db: &sled::Db, account_id: &AccountId, image_id: &[u32; 8]) {
+    db.insert(contract_key(account_id), borsh::to_vec(image_id).unwrap())
+        .expect("Failed to insert contract code to db");
+}
+
+/// Moves contracts deployed before the code store to it, their entries hold the ELF itself.
+/// The system meta-contract doesn't count them, so their images are never removed
+pub fn migrate_legacy_contracts(db: &sled::Db) {
+    for entry in db.scan_prefix("contracts_code.") {
+        let (key, value) = entry.expect("Failed to read contract code from db");
+        if borsh::from_slice::<[u32; 8]>(&value).is_ok() {
+            continue;
+        }
+
+        let program =
+            risc0_zkvm::Program::load_elf(&value, MAX_MEMORY).expect("Corrupted contract code");
+        let image = risc0_zkvm::MemoryImage::new(&program, PAGE_SIZE).unwrap();
+        let image_id: [u32; 8] = image.compute_id().as_words().try_into().unwrap();
+        info!(
+            contract = String::from_utf8_lossy(&key).as_ref(),
+            image_id = image_id_hex(&image_id),
+            "Migrating contract code"
+        );
+
+        insert(db, &image_id, value.to_vec());
+        db.insert(key, borsh::to_vec(&image_id).unwrap())
+            .expect("Failed to insert contract code to db");
+    }
+}
+
+/// Restores contract images replaced by a failed call, the last replacement is reverted first
+pub fn revert_contract_code(db: &sled::Db, replaced: Vec<(AccountId, Option<[u32; 8]>)>) {
+    for (account_id, image_id) in replaced.into_iter().rev() {
+        match image_id {
+            Some(image_id) => set_contract_code(db, &account_id, &image_id),
+            None => {
+                db.remove(contract_key(&account_id))
+                    .expect("Failed to remove contract code from db");
+            }
+        }
+    }
+}
@@ -5,17 +5,24 @@ use tracing::{debug, info, span, Level};
 
 use multivm_primitives::{
     syscalls::{
-        CrossContractCallRequest, GetStorageResponse, SetStorageRequest, CROSS_CONTRACT_CALL,
+        CommitStorageRequest, CrossContractCallRequest, DeleteStorageRequest,
+        DeployContractRequest, GetStorageResponse, SetStorageRequest, CALL_CYCLES_CALL,
+        COMMIT_STORAGE_CALL, CROSS_CONTRACT_CALL, DELETE_STORAGE_CALL, DEPLOY_CONTRACT_CALL,
         GET_STORAGE_CALL, SET_STORAGE_CALL,
     },
-    AccountId, ContractCallContext,
+    AccountId, Commitment, ContractCall, ContractCallContext, StorageUsageUpdate,
 };
 
 use crate::{
-    account::Executable, bootstraper::Action, outcome::ExecutionOutcome, utils, viewer::Viewer,
+    account::Executable,
+    bootstraper::Action,
+    code_store,
+    outcome::{CallInfo, CallRecord, ExecutionOutcome},
+    storage, utils,
+    viewer::Viewer,
 };
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 const MAX_MEMORY: u32 = 0x10000000;
 const PAGE_SIZE: u32 = 0x400;
@@ -24,6 +31,9 @@ pub struct Executor {
     context: ContractCallContext,
     db: sled::Db,
     cross_calls_outcomes: Rc<RefCell<Vec<ExecutionOutcome>>>,
+    record: Rc<RefCell<CallRecord>>,
+    /// Previous images of the contracts deployed during the call
+    replaced_code: RefCell<Vec<(AccountId, Option<[u32; 8]>)>>,
 }
 
 impl Executor {
@@ -32,6 +42,8 @@ impl Executor {
             context,
             db,
             cross_calls_outcomes: Default::default(),
+            record: Default::default(),
+            replaced_code: Default::default(),
         }
     }
 
@@ -72,7 +84,14 @@ impl Executor {
             .io_callback(CROSS_CONTRACT_CALL, self.callback_on_cross_contract_call())
             .io_callback(GET_STORAGE_CALL, self.callback_on_get_storage())
             .io_callback(SET_STORAGE_CALL, self.callback_on_set_storage())
-            .stdout(ContractLogger::new(self.context.contract_id.clone()))
+            .io_callback(COMMIT_STORAGE_CALL, self.callback_on_commit_storage())
+            .io_callback(DELETE_STORAGE_CALL, self.callback_on_delete_storage())
+            .io_callback(DEPLOY_CONTRACT_CALL, self.callback_on_contract_deployment())
+            .io_callback(CALL_CYCLES_CALL, self.callback_on_call_cycles())
+            .stdout(ContractLogger::new(
+                self.context.contract_id.clone(),
+                self.record.clone(),
+            ))
             .build()
             .unwrap();
 
@@ -84,27 +103,55 @@ impl Executor {
 
         let session = exec.execute(env, image).unwrap();
 
-        Ok(ExecutionOutcome::new(
+        let outcome = ExecutionOutcome::new(
             session,
             0,
             self.cross_calls_outcomes.take(),
-        ))
+            self.call_info(),
+            self.record.take(),
+        );
+
+        if outcome.commitment.response.is_err() {
+            code_store::revert_contract_code(&self.db, self.replaced_code.take());
+        }
+
+        Ok(outcome)
+    }
+
+    fn call_info(&self) -> CallInfo {
+        CallInfo {
+            contract_id: self.context.contract_id.clone(),
+            method: self.context.contract_call.method.clone(),
+            args_hash: Sha256::digest(&self.context.contract_call.args).into(),
+            caller: self.context.sender_id.clone(),
+            gas: self.context.contract_call.gas,
+        }
     }
 
     fn load_contract(&self, contract_id: AccountId) -> Result<Vec<u8>> {
-        let db_key = format!("contracts_code.{}", contract_id.to_string());
-        info!(db_key, "load contract");
+        info!(contract = ?contract_id, "load contract");
 
-        let code = self
-            .db
-            .get(db_key)
-            .expect("Failed to get storage from db")
-            .map(|v| v.to_vec())
-            .expect("Contract not found");
+        let code = code_store::contract_code(&self.db, &contract_id).expect("Contract not found");
 
         Ok(code)
     }
 
+    /// Cycles of the last cross-contract call, EVM contracts charge MultiVM calls with them
+    pub fn callback_on_call_cycles<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |_from_guest| {
+            let cycles = self
+                .cross_calls_outcomes
+                .borrow()
+                .last()
+                .map(ExecutionOutcome::total_cycles)
+                .unwrap_or_default();
+
+            Ok(borsh::to_vec(&cycles).unwrap().into())
+        }
+    }
+
     pub fn callback_on_cross_contract_call<'a>(
         &'a self,
     ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
@@ -136,6 +183,98 @@ impl Executor {
         }
     }
 
+    pub fn callback_on_contract_deployment<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |from_guest| {
+            let span = span!(Level::DEBUG, "contract_deployment call handler");
+            let _enter = span.enter();
+
+            let request: DeployContractRequest =
+                BorshDeserialize::try_from_slice(&from_guest).unwrap();
+
+            if self.context.contract_id == AccountId::system_meta_contract() {
+                // TODO: error handling
+                if code_store::get(&self.db, &request.image_id).is_none() {
+                    panic!("Contract image is not uploaded");
+                }
+
+                self.replaced_code.borrow_mut().push((
+                    request.account_id.clone(),
+                    code_store::contract_image_id(&self.db, &request.account_id),
+                ));
+                code_store::set_contract_code(&self.db, &request.account_id, &request.image_id);
+
+                return Ok(Default::default());
+            }
+
+            // Contracts can't change accounts, the system meta-contract creates the sub-account
+            let call_context = ContractCallContext {
+                contract_id: AccountId::system_meta_contract(),
+                contract_call: ContractCall::new_call("create_sub_contract", &request),
+                sender_id: self.context.contract_id.clone(),
+                signer_id: self.context.signer_id.clone(),
+                environment: self.context.environment.clone(),
+            };
+
+            debug!(call_context=?call_context, "Deploying sub-contract");
+
+            let outcome = Executor::new(call_context, self.db.clone())
+                .execute()
+                .context("Sub-contract deployment failed")?;
+
+            let commitment = borsh::to_vec(&outcome.commitment).unwrap();
+
+            (self.cross_calls_outcomes.borrow_mut()).push(outcome);
+
+            Ok(commitment.into())
+        }
+    }
+
+    /// Returns the account under which the current contract storage is kept
+    fn storage_location(&self) -> AccountId {
+        if self.context.contract_id == AccountId::system_meta_contract() {
+            return AccountId::system_meta_contract();
+        }
+
+        let contract = Viewer::account_info(&self.context.contract_id, self.db.clone())
+            .expect("Loading storage for non-existent contract");
+
+        match contract.executable {
+            Some(Executable::MultiVm(_)) => contract
+                .multivm_account_id
+                .expect("Contract without MultiVmAccountId")
+                .into(),
+            Some(Executable::Evm()) => AccountId::system_meta_contract(),
+            None => unreachable!("Loading storage for non-executable account"),
+        }
+    }
+
+    /// Applies the storage size change to the contract account through the system meta-contract,
+    /// the call fails if the account balance doesn't cover its storage
+    fn update_storage_usage(&self, account_id: AccountId, delta: i64) -> Result<Commitment> {
+        let call_context = ContractCallContext {
+            contract_id: AccountId::system_meta_contract(),
+            contract_call: ContractCall::new_call(
+                "update_storage_usage",
+                &StorageUsageUpdate { account_id, delta },
+            ),
+            sender_id: self.context.contract_id.clone(),
+            // Transactions can't be signed by the system, so only the node can change the usage
+            signer_id: AccountId::system_meta_contract(),
+            environment: self.context.environment.clone(),
+        };
+
+        let outcome = Executor::new(call_context, self.db.clone())
+            .execute()
+            .context("Storage usage update failed")?;
+        let commitment = outcome.commitment.clone();
+
+        (self.cross_calls_outcomes.borrow_mut()).push(outcome);
+
+        Ok(commitment)
+    }
+
     pub fn callback_on_get_storage<'a>(
         &'a self,
     ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
@@ -145,22 +284,7 @@ impl Executor {
 
             let key = String::from_utf8(from_guest.into()).unwrap();
 
-            let storage_location = if self.context.contract_id == AccountId::system_meta_contract()
-            {
-                AccountId::system_meta_contract()
-            } else {
-                let contract = Viewer::account_info(&self.context.contract_id, self.db.clone())
-                    .expect("Loading storage for non-existent contract");
-
-                match contract.executable {
-                    Some(Executable::MultiVm(_)) => contract
-                        .multivm_account_id
-                        .expect("Contract without MultiVmAccountId")
-                        .into(),
-                    Some(Executable::Evm()) => AccountId::system_meta_contract(),
-                    None => unreachable!("Loading storage for non-executable account"),
-                }
-            };
+            let storage_location = self.storage_location();
 
             let db_key = format!("committed_storage.{}.{}", storage_location, key);
 
@@ -182,6 +306,8 @@ impl Executor {
 
             debug!(contract=?storage_location, key=?key, "Loading storage");
 
+            self.record.borrow_mut().storage_reads.push(key);
+
             Ok(response_bytes.into())
         }
     }
@@ -195,48 +321,143 @@ impl Executor {
 
             let request: SetStorageRequest = BorshDeserialize::try_from_slice(&from_guest).unwrap();
 
+            let commitment = self.write_storage(vec![request])?;
+
+            Ok(borsh::to_vec(&commitment).unwrap().into())
+        }
+    }
+
+    pub fn callback_on_delete_storage<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |from_guest| {
+            let span = span!(Level::DEBUG, "delete_storage call handler");
+            let _enter = span.enter();
+
+            let request: DeleteStorageRequest =
+                BorshDeserialize::try_from_slice(&from_guest).unwrap();
+
+            let storage_location = self.storage_location();
+
+            // Freed bytes unlock the contract balance
+            let previous = storage::get(&self.db, &storage_location, &request.key);
+            let delta = -storage_size(&request.key, previous.as_deref());
+            let commitment = self.charge_storage(storage_location.clone(), delta)?;
+            if let Some(Err(error)) = commitment.as_ref().map(|commitment| &commitment.response) {
+                debug!(contract=?storage_location, delta, error=?error, "Storage deletion rejected");
+                return Ok(Default::default());
+            }
+
+            debug!(contract=?storage_location, key=?request.key, "Deleting storage");
+
+            storage::remove(
+                &self.db,
+                &storage_location,
+                &request.key,
+                self.context.environment.block_height,
+            );
+
+            self.record.borrow_mut().storage_writes.push(request.key);
+
+            Ok(Default::default())
+        }
+    }
+
+    pub fn callback_on_commit_storage<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |from_guest| {
+            let span = span!(Level::DEBUG, "commit_storage call handler");
+            let _enter = span.enter();
+
+            let request: CommitStorageRequest =
+                BorshDeserialize::try_from_slice(&from_guest).unwrap();
+
+            let commitment = self.write_storage(request.updates)?;
+
+            Ok(borsh::to_vec(&commitment).unwrap().into())
+        }
+    }
+
+    /// Writes the storage updates of the current contract, the usage is updated once for all
+    /// of them. Nothing is written if the contract balance doesn't cover the new usage
+    fn write_storage(&self, updates: Vec<SetStorageRequest>) -> Result<Option<Commitment>> {
+        let storage_location = self.storage_location();
+
+        // The same key may be updated several times, each update replaces the previous one
+        let mut sizes = HashMap::new();
+        let mut delta = 0;
+        for update in &updates {
             let algorithm = &mut Sha256::default();
-            algorithm.update(request.storage.clone());
-            let hash2 = algorithm.finalize_reset();
-            assert_eq!(request.hash, hash2.as_slice());
-
-            let storage_location = if self.context.contract_id == AccountId::system_meta_contract()
-            {
-                AccountId::system_meta_contract()
-            } else {
-                let contract = Viewer::account_info(&self.context.contract_id, self.db.clone())
-                    .expect("Loading storage for non-existent contract");
-
-                match contract.executable {
-                    Some(Executable::MultiVm(_)) => contract
-                        .multivm_account_id
-                        .expect("Contract without MultiVmAccountId")
-                        .into(),
-                    Some(Executable::Evm()) => AccountId::system_meta_contract(),
-                    None => unreachable!("Loading storage for non-executable account"),
+            algorithm.update(&update.storage);
+            assert_eq!(update.hash, algorithm.finalize_reset().as_slice());
+
+            let previous = match sizes.get(update.key.as_str()) {
+                Some(size) => *size,
+                None => {
+                    let previous = storage::get(&self.db, &storage_location, &update.key);
+                    storage_size(&update.key, previous.as_deref())
                 }
             };
+            let size = storage_size(&update.key, Some(&update.storage));
+            delta += size - previous;
+            sizes.insert(update.key.as_str(), size);
+        }
 
-            debug!(contract=?storage_location, key=?request.key, new_hash = utils::bytes_to_hex(hash2.as_slice()), "Updating storage");
+        let commitment = self.charge_storage(storage_location.clone(), delta)?;
+        if let Some(Err(error)) = commitment.as_ref().map(|commitment| &commitment.response) {
+            debug!(contract=?storage_location, delta, error=?error, "Storage commit rejected");
+            return Ok(commitment);
+        }
 
-            let db_key = format!("committed_storage.{}.{}", storage_location, request.key);
+        for update in updates {
+            debug!(contract=?storage_location, key=?update.key, new_hash = utils::bytes_to_hex(&update.hash), "Updating storage");
 
-            self.db
-                .insert(db_key, request.storage)
-                .expect("Failed to insert storage to db");
+            storage::insert(
+                &self.db,
+                &storage_location,
+                &update.key,
+                self.context.environment.block_height,
+                update.storage,
+            );
 
-            Ok(Default::default())
+            self.record.borrow_mut().storage_writes.push(update.key);
+        }
+
+        Ok(commitment)
+    }
+
+    /// Applies the storage size change to the contract account. The system meta-contract and
+    /// EVM contracts keep their storage under the system account, it isn't charged
+    fn charge_storage(
+        &self,
+        storage_location: AccountId,
+        delta: i64,
+    ) -> Result<Option<Commitment>> {
+        if delta == 0 || storage_location == AccountId::system_meta_contract() {
+            return Ok(None);
         }
+
+        self.update_storage_usage(storage_location, delta).map(Some)
     }
 }
 
+/// Bytes the storage entry takes, its key included
+fn storage_size(key: &str, value: Option<&[u8]>) -> i64 {
+    value.map_or(0, |value| (key.len() + value.len()) as i64)
+}
+
 pub struct ContractLogger {
     pub contract_id: AccountId,
+    record: Rc<RefCell<CallRecord>>,
 }
 
 impl ContractLogger {
-    pub fn new(contract_id: AccountId) -> Self {
-        Self { contract_id }
+    pub fn new(contract_id: AccountId, record: Rc<RefCell<CallRecord>>) -> Self {
+        Self {
+            contract_id,
+            record,
+        }
     }
 }
 
@@ -247,6 +468,8 @@ impl std::io::Write for ContractLogger {
 
         tracing::info!(contract_id = ?self.contract_id, msg, "📜 Contract log");
 
+        self.record.borrow_mut().logs.push(msg);
+
         Ok(buf.len())
     }
 
@@ -4,23 +4,30 @@ use account::Account;
 use block::UnprovedBlock;
 use bootstraper::Bootstraper;
 use borsh::{BorshDeserialize, BorshSerialize};
+use executor::Executor;
 use multivm_primitives::{
-    AccountId, Block, ContractResponse, EnvironmentContext, SupportedTransaction,
+    logs_bloom, AccessKey, AccountId, Block, CodeVersion, ContractCall, ContractCallContext,
+    ContractResponse, Digest, EnvironmentContext, EvmAddress, EvmTrace, Multisig,
+    SignedTransaction, StoredCode, SupportedTransaction, Validator, EPOCH_LENGTH,
 };
+use risc0_zkvm::sha::{Impl as HashImpl, Sha256};
 use tracing::{debug, info};
 use viewer::{SupportedView, Viewer};
 
 pub mod account;
 pub mod block;
 pub mod bootstraper;
+pub mod code_store;
 pub mod executor;
 pub mod outcome;
+pub mod storage;
 pub mod utils;
 pub mod viewer;
 
 pub struct MultivmNode {
     db: sled::Db,
     txs_pool: std::collections::VecDeque<SupportedTransaction>,
+    trace: bool,
 }
 
 impl MultivmNode {
@@ -30,10 +37,14 @@ impl MultivmNode {
         let mut node = Self {
             db: sled::open(db_path).unwrap(),
             txs_pool: std::collections::VecDeque::new(),
+            trace: false,
         };
 
         if !node.db.was_recovered() {
             node.init_genesis();
+        } else {
+            code_store::migrate_legacy_contracts(&node.db);
+            node.index_legacy_blocks();
         }
 
         node
@@ -50,12 +61,18 @@ impl MultivmNode {
             timestamp: 0,
             txs: Default::default(),
             call_outputs: Default::default(),
+            receipts: Default::default(),
+            logs_bloom: logs_bloom(&[]),
+            producer: None,
         };
 
         self.insert_block(genesis_block);
+        self.db.insert(b"blocks_indexed", &[]).unwrap();
     }
 
     fn insert_block(&mut self, block: Block) {
+        self.index_block(&block);
+
         self.db
             .insert(
                 format!("block_{}", block.height),
@@ -80,6 +97,67 @@ impl MultivmNode {
         block
     }
 
+    fn transaction_height_key(tx_hash: &Digest) -> String {
+        format!("transaction_height.{}", utils::bytes_to_hex(tx_hash))
+    }
+
+    fn block_height_key(block_hash: &Digest) -> String {
+        format!("block_height.{}", utils::bytes_to_hex(block_hash))
+    }
+
+    /// Indexes the block and its transactions by hash
+    fn index_block(&self, block: &Block) {
+        self.db
+            .insert(
+                Self::block_height_key(&block.hash),
+                &block.height.to_be_bytes(),
+            )
+            .unwrap();
+        for tx in block.txs.iter() {
+            self.db
+                .insert(
+                    Self::transaction_height_key(&tx.hash()),
+                    &block.height.to_be_bytes(),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Indexes blocks produced before the index was kept
+    fn index_legacy_blocks(&self) {
+        if self.db.contains_key(b"blocks_indexed").unwrap() {
+            return;
+        }
+
+        info!("Indexing stored blocks");
+        for height in 0..=self.latest_block().height {
+            let block = self.block_by_height(height).expect("Block not found");
+            self.index_block(&block);
+        }
+
+        self.db.insert(b"blocks_indexed", &[]).unwrap();
+        self.db.flush().unwrap();
+    }
+
+    fn height_by_key(&self, key: String) -> Option<u64> {
+        self.db
+            .get(key)
+            .unwrap()
+            .map(|bytes| u64::from_be_bytes(bytes.as_ref().try_into().unwrap()))
+    }
+
+    pub fn block_by_hash(&self, block_hash: &Digest) -> Option<Block> {
+        let height = self.height_by_key(Self::block_height_key(block_hash))?;
+        self.block_by_height(height)
+    }
+
+    /// Returns the block including the transaction
+    pub fn block_by_transaction(&self, tx_hash: &Digest) -> Option<Block> {
+        let height = self.height_by_key(Self::transaction_height_key(tx_hash))?;
+
+        self.block_by_height(height)
+    }
+
     pub fn latest_block(&self) -> Block {
         let mut latest_block_bytes = self.db.get(b"latest_block").unwrap().unwrap().to_vec();
         let latest_block: Block = borsh::from_slice(&mut latest_block_bytes).unwrap();
@@ -87,8 +165,126 @@ impl MultivmNode {
         latest_block
     }
 
-    pub fn add_tx(&mut self, tx: SupportedTransaction) {
+    pub fn add_tx(&mut self, tx: SupportedTransaction) -> anyhow::Result<()> {
+        if let SupportedTransaction::MultiVm(signed_tx) = &tx {
+            self.check_signatures(signed_tx)?;
+        }
+
+        if let SupportedTransaction::Delegated(delegated_tx) = &tx {
+            let relayer_id = &delegated_tx.relayer_id;
+            let relayer = EvmAddress::from(delegated_tx.recover_relayer()?);
+            let Some(keys) = self.access_keys(relayer_id) else {
+                anyhow::bail!("Relayer not found: {}", relayer_id);
+            };
+            if !keys.contains(&AccessKey::full_access(relayer.clone())) {
+                anyhow::bail!(
+                    "Full access key {} not found for relayer {}",
+                    relayer,
+                    relayer_id
+                );
+            }
+
+            self.check_signatures(&delegated_tx.transaction)?;
+        }
+
+        if let SupportedTransaction::Evm(request) = &tx {
+            let (eth_tx, _) = request.decode()?;
+            let from = *eth_tx
+                .from()
+                .ok_or_else(|| anyhow::anyhow!("Transaction sender not recovered"))?;
+            let sender = EvmAddress::from(from);
+            if self.multisig(&sender.clone().into()).is_some() {
+                anyhow::bail!("Multisig accounts can't send EVM transactions");
+            }
+            // Alias keys may be rotated or removed, then the address loses control of the account
+            if let Some(keys) = self.access_keys(&sender.clone().into()) {
+                if !keys.contains(&AccessKey::full_access(sender.clone())) {
+                    anyhow::bail!("Full access key {} not found for its account", sender);
+                }
+            }
+            let nonce = eth_tx.nonce().cloned().unwrap_or_default();
+            // Nonces above the account one are left for the system contract to reject,
+            // they may become valid once pending transactions are included
+            if let Some(account) = self.account_info(&sender.into()) {
+                if nonce < account.nonce.into() {
+                    anyhow::bail!(
+                        "Nonce too low: account nonce is {}, got {}",
+                        account.nonce,
+                        nonce
+                    );
+                }
+            }
+        }
+
         self.txs_pool.push_back(tx);
+
+        Ok(())
+    }
+
+    /// Nonce of the next transaction signed by the account, counting the pending ones
+    pub fn pending_nonce(&self, account_id: &AccountId) -> u64 {
+        let nonce = self
+            .account_info(account_id)
+            .map(|account| account.nonce)
+            .unwrap_or_default();
+        let pending = self
+            .txs_pool
+            .iter()
+            .filter(|tx| match tx {
+                SupportedTransaction::MultiVm(signed_tx) => {
+                    signed_tx.transaction.signer_id == *account_id
+                }
+                SupportedTransaction::Delegated(delegated_tx) => {
+                    delegated_tx.transaction.transaction.signer_id == *account_id
+                }
+                SupportedTransaction::Evm(_) => false,
+            })
+            .count();
+
+        nonce + pending as u64
+    }
+
+    /// Checks transaction signatures against the signer account keys
+    fn check_signatures(&self, signed_tx: &SignedTransaction) -> anyhow::Result<()> {
+        let signer_id = &signed_tx.transaction.signer_id;
+        let mut signers = signed_tx.signers()?;
+
+        // Signer may be created by a pending transaction, then its keys are checked
+        // by the system contract only. Key permissions are always checked there
+        if let Some(multisig) = self.multisig(signer_id) {
+            signers.sort();
+            signers.dedup();
+            let approvals = signers
+                .iter()
+                .filter(|signer| multisig.keys.contains(signer))
+                .count();
+            if approvals < multisig.threshold as usize {
+                anyhow::bail!(
+                    "Not enough multisig signatures: {} of {}",
+                    approvals,
+                    multisig.threshold
+                );
+            }
+        } else if let Some(keys) = self.access_keys(signer_id) {
+            let address = &signers[0];
+            if !keys.iter().any(|key| key.address == *address) {
+                anyhow::bail!("Access key {} not found for {}", address, signer_id);
+            }
+        }
+
+        // Greater nonces may become valid once pending transactions are included
+        let nonce = signed_tx.transaction.nonce;
+        if let Some(account) = self.account_info(signer_id) {
+            if nonce < account.nonce {
+                anyhow::bail!(
+                    "Nonce too low: account nonce is {}, got {}",
+                    account.nonce,
+                    nonce
+                );
+            }
+        }
+
+        Ok(())
     }
 
     fn environment(&self) -> EnvironmentContext {
@@ -97,18 +293,43 @@ impl MultivmNode {
         }
     }
 
+    /// Enables EVM tracing of transactions in blocks produced without proofs and of views.
+    /// Debug mode only, requires `evm-tracing` feature
+    pub fn enable_tracing(&mut self) {
+        assert!(
+            cfg!(feature = "evm-tracing"),
+            "Node is built without evm-tracing feature"
+        );
+        self.trace = true;
+    }
+
     pub fn produce_block(&mut self, skip_proof: bool) -> Block {
         let latest_block = self.latest_block();
         info!(height = latest_block.height + 1, "Creating new block");
         let start: std::time::Instant = std::time::Instant::now();
+
+        // The set is fixed by the first block of the epoch, before its transactions change it.
+        // The first block of the chain snapshots epoch 0
+        let epoch = (latest_block.height + 1) / EPOCH_LENGTH;
+        if self.epoch_validators(epoch).is_none() {
+            self.snapshot_validators(epoch);
+        }
+        // TODO: let only the selected validator produce the block once there is more than one node
+        let producer = self.block_producer(latest_block.height + 1);
+        debug!(epoch, producer = ?producer, "Block producer");
         // self.txs_pool = Default::default();
         let (txs, execution_outcomes): (Vec<_>, Vec<_>) = self
             .txs_pool
             .iter()
             .map(|tx| {
-                let outcome =
-                    Bootstraper::new(self.db.clone(), tx.clone(), tx.signer(), self.environment())
-                        .bootstrap();
+                let bootstraper =
+                    Bootstraper::new(self.db.clone(), tx.clone(), tx.signer(), self.environment());
+                // Tracing doesn't affect execution, but it is not proven
+                let outcome = if self.trace && skip_proof {
+                    bootstraper.with_trace().bootstrap()
+                } else {
+                    bootstraper.bootstrap()
+                };
                 (tx.clone(), outcome)
             })
             .unzip();
@@ -138,6 +359,7 @@ impl MultivmNode {
             previous_global_root: Default::default(),
             new_global_root: Default::default(),
             execution_outcomes,
+            producer,
         };
 
         let block = unproved_block.prove(skip_proof);
@@ -151,19 +373,482 @@ impl MultivmNode {
         Viewer::account_info(account_id, self.db.clone())
     }
 
+    pub fn account_info_at(&self, account_id: &AccountId, height: Option<u64>) -> Option<Account> {
+        Viewer::account_info_at(account_id, self.db.clone(), height)
+    }
+
+    /// Returns keys allowed to sign transactions of the account, `None` if account doesn't exist
+    pub fn access_keys(&self, account_id: &AccountId) -> Option<Vec<AccessKey>> {
+        let bytes = self
+            .system_view("access_keys".to_string(), account_id)
+            .expect("Failed to view access keys");
+        borsh::from_slice(&bytes).expect("Corrupted access keys")
+    }
+
+    /// Returns multisig configuration of the account, `None` for regular accounts
+    pub fn multisig(&self, account_id: &AccountId) -> Option<Multisig> {
+        let bytes = self
+            .system_view("multisig".to_string(), account_id)
+            .expect("Failed to view multisig");
+        borsh::from_slice(&bytes).expect("Corrupted multisig")
+    }
+
+    /// Returns code versions of the contract, `None` if account doesn't exist
+    pub fn code_history(&self, account_id: &AccountId) -> Option<Vec<CodeVersion>> {
+        let bytes = self
+            .system_view("code_history".to_string(), account_id)
+            .expect("Failed to view code history");
+        borsh::from_slice(&bytes).expect("Corrupted code history")
+    }
+
+    /// Returns the code store entry of the image, `None` if it is not uploaded
+    pub fn stored_code(&self, image_id: &[u32; 8]) -> Option<StoredCode> {
+        let bytes = self
+            .system_view("stored_code".to_string(), image_id)
+            .expect("Failed to view stored code");
+        borsh::from_slice(&bytes).expect("Corrupted stored code")
+    }
+
+    /// Returns the native token supply, the sum of all account balances, stakes and unbonding
+    /// tokens
+    pub fn total_supply(&self) -> u128 {
+        let bytes = self
+            .system_view("total_supply".to_string(), &())
+            .expect("Failed to view total supply");
+        borsh::from_slice(&bytes).expect("Corrupted total supply")
+    }
+
+    /// Returns the current validator set, changed by staking transactions
+    pub fn validators(&self) -> Vec<Validator> {
+        let bytes = self
+            .system_view("validators".to_string(), &())
+            .expect("Failed to view validators");
+        borsh::from_slice(&bytes).expect("Corrupted validators")
+    }
+
+    /// Returns the validator set of the epoch, `None` if it is not snapshotted yet
+    pub fn epoch_validators(&self, epoch: u64) -> Option<Vec<Validator>> {
+        let bytes = self
+            .system_view("epoch_validators".to_string(), &epoch)
+            .expect("Failed to view epoch validators");
+        borsh::from_slice(&bytes).expect("Corrupted epoch validators")
+    }
+
+    /// Returns the validator producing the block, chosen from the epoch snapshot with
+    /// the probability of its stake share, `None` if nobody stakes
+    pub fn block_producer(&self, height: u64) -> Option<AccountId> {
+        let validators = self.epoch_validators(height / EPOCH_LENGTH)?;
+        let total_stake: u128 = validators.iter().map(|validator| validator.stake).sum();
+        if total_stake == 0 {
+            return None;
+        }
+
+        // Every node selects the same validator for the height
+        let seed = HashImpl::hash_bytes(&height.to_le_bytes());
+        let mut point =
+            u128::from_le_bytes(seed.as_bytes()[..16].try_into().unwrap()) % total_stake;
+        for validator in validators {
+            if point < validator.stake {
+                return Some(validator.account_id);
+            }
+            point -= validator.stake;
+        }
+
+        unreachable!("Point is within the total stake")
+    }
+
+    /// Snapshot is a part of the first block of the epoch, its history is recorded at the block
+    fn snapshot_validators(&mut self, epoch: u64) {
+        let environment = self.environment();
+        self.system_call(
+            ContractCall::new_call("snapshot_validators", &epoch),
+            environment,
+        )
+        .expect("Failed to snapshot validators");
+    }
+
     pub fn system_view<T: BorshSerialize>(&self, method: String, args: &T) -> ContractResponse {
         Viewer::view_system_meta_contract(method, args, self.db.clone())
     }
 
+    /// Views system meta-contract at the given block height, `None` for the latest state
+    pub fn system_view_at<T: BorshSerialize>(
+        &self,
+        method: String,
+        args: &T,
+        height: Option<u64>,
+    ) -> ContractResponse {
+        Viewer::view_system_meta_contract_at(method, args, self.db.clone(), height)
+    }
+
+    /// Executes system meta-contract call outside of transactions, initializes genesis state
+    pub fn genesis_call(&mut self, call: ContractCall) -> ContractResponse {
+        let environment = EnvironmentContext {
+            block_height: self.latest_block().height,
+        };
+        self.system_call(call, environment)
+    }
+
+    /// Calls the system meta-contract as the system itself, for the methods only the node may call
+    fn system_call(
+        &mut self,
+        call: ContractCall,
+        environment: EnvironmentContext,
+    ) -> ContractResponse {
+        let context = ContractCallContext {
+            contract_id: AccountId::system_meta_contract(),
+            contract_call: call,
+            sender_id: AccountId::system_meta_contract(),
+            signer_id: AccountId::system_meta_contract(),
+            environment,
+        };
+
+        Executor::new(context, self.db.clone())
+            .execute()
+            .expect("System call failed")
+            .commitment
+            .response
+    }
+
     pub fn contract_view(&self, view: SupportedView) -> ContractResponse {
         Viewer::new(view, self.db.clone()).view()
     }
+
+    /// Returns trace of EVM transaction, traces are recorded for blocks produced without proofs
+    /// by nodes with tracing enabled
+    pub fn evm_trace(&self, tx_hash: &Digest) -> Option<EvmTrace> {
+        let db_key = format!("evm_traces.{}", utils::bytes_to_hex(tx_hash));
+
+        self.db
+            .get(db_key)
+            .expect("Failed to get EVM trace from db")
+            .map(|bytes| EvmTrace::try_from_slice(&bytes).expect("Corrupted EVM trace"))
+    }
+
+    pub fn trace_view(&self, view: SupportedView) -> Option<EvmTrace> {
+        if !self.trace {
+            return None;
+        }
+
+        Viewer::new(view, self.db.clone()).trace()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use ethers_core::{
+        abi::Token,
+        types::{Signature, TransactionRequest, U256},
+    };
+    use multivm_primitives::{
+        k256::ecdsa::SigningKey,
+        solidity_abi,
+        syscalls::{CommitStorageRequest, DeleteStorageRequest, SetStorageRequest},
+        AccessKeyPermission, AccountCreationParams, Attachments, Commitment, DelegatedTransaction,
+        EthereumTransactionRequest, MultiVmAccountId, TransactionBuilder, Unbonding,
+        BASE_FEE_PER_GAS, CHAIN_ID, ONE_TOKEN, STORAGE_PRICE_PER_BYTE, UNBONDING_PERIOD,
+    };
+
     use super::*;
 
+    const SUPER_ACCOUNT_SK: &str =
+        "4146c7e323d0ddae7baebd8e0dccbee723c9795c904d004e43a33e17adc8aa2e";
+    const ALICE_SK: &str = "8a9e1c0e5a2c3b7d4f6e8a1b3c5d7e9f0a2b4c6d8e0f1a3b5c7d9e1f3a5b7c9d";
+    const ALICE_SECOND_SK: &str =
+        "2f1d3c5b7a9e8d6c4b2a0f1e3d5c7b9a8e6d4c2b0a1f3e5d7c9b8a6e4d2c0b1a";
+
+    fn signing_key(hex_key: &str) -> SigningKey {
+        SigningKey::from_slice(&hex::decode(hex_key).unwrap()).unwrap()
+    }
+
+    fn address(hex_key: &str) -> EvmAddress {
+        (*signing_key(hex_key).verifying_key()).into()
+    }
+
+    fn account_id(name: &str) -> AccountId {
+        MultiVmAccountId::try_from(name).unwrap().into()
+    }
+
+    fn balance(node: &MultivmNode, account_id: &AccountId) -> u128 {
+        node.account_info(account_id)
+            .map(|account| account.balance)
+            .unwrap_or_default()
+    }
+
+    /// Node with the super account funded at genesis, collecting fees and charging for account
+    /// creation
+    fn genesis_node(db_path: &str) -> MultivmNode {
+        let _ = std::fs::remove_dir_all(db_path);
+        let mut node = MultivmNode::new(db_path.to_string());
+
+        node.genesis_call(ContractCall::new(
+            "init_debug_account".into(),
+            &address(SUPER_ACCOUNT_SK),
+            100_000_000,
+            0,
+        ))
+        .expect("Failed to create super account");
+        node.genesis_call(ContractCall::new(
+            "set_registrar".into(),
+            &account_id("super.multivm"),
+            100_000_000,
+            0,
+        ))
+        .expect("Failed to set registrar");
+        node.genesis_call(ContractCall::new(
+            "set_fee_collector".into(),
+            &account_id("super.multivm"),
+            100_000_000,
+            0,
+        ))
+        .expect("Failed to set fee collector");
+        node.genesis_call(ContractCall::new(
+            "set_account_creation_params".into(),
+            &AccountCreationParams {
+                initial_balance: 1_000 * ONE_TOKEN,
+                creation_fee: ONE_TOKEN,
+            },
+            100_000_000,
+            0,
+        ))
+        .expect("Failed to set account creation params");
+
+        node
+    }
+
+    /// Includes the transaction into a new block, the error is either the pool rejection
+    /// or the failed execution
+    fn submit(node: &mut MultivmNode, tx: SupportedTransaction) -> Result<(), String> {
+        let tx_hash = tx.hash();
+        node.add_tx(tx).map_err(|error| error.to_string())?;
+        let block = node.produce_block(true);
+        match block.call_outputs.get(&tx_hash).expect("Output not found") {
+            Ok(_) => Ok(()),
+            Err(error) => Err(format!("{:?}", error)),
+        }
+    }
+
+    fn send_to(
+        node: &mut MultivmNode,
+        signer: &str,
+        sk: &str,
+        receiver_id: AccountId,
+        call: ContractCall,
+    ) -> Result<(), String> {
+        let tx = TransactionBuilder::new(
+            receiver_id,
+            vec![call],
+            account_id(signer),
+            &node.latest_block(),
+        )
+        .set_nonce(node.pending_nonce(&account_id(signer)))
+        .build();
+        let tx = SignedTransaction::new(tx, &signing_key(sk));
+
+        submit(node, tx.into())
+    }
+
+    fn send(
+        node: &mut MultivmNode,
+        signer: &str,
+        sk: &str,
+        call: ContractCall,
+    ) -> Result<(), String> {
+        send_to(node, signer, sk, AccountId::system_meta_contract(), call)
+    }
+
+    /// Legacy EIP-155 value transfer signed by the key
+    fn send_evm(
+        node: &mut MultivmNode,
+        sk: &str,
+        to: EvmAddress,
+        value: u128,
+    ) -> Result<(), String> {
+        send_evm_with_gas(node, sk, to, value, 21_000)
+    }
+
+    fn send_evm_with_gas(
+        node: &mut MultivmNode,
+        sk: &str,
+        to: EvmAddress,
+        value: u128,
+        gas: u64,
+    ) -> Result<(), String> {
+        let tx = evm_request(node, sk, gas)
+            .to(eth_primitive_types::H160::from(to))
+            .value(value);
+
+        submit(node, sign_evm(sk, tx))
+    }
+
+    /// Deploys EVM contract, returns the address reported by the deployment
+    fn deploy_evm(node: &mut MultivmNode, sk: &str, code: Vec<u8>) -> [u8; 20] {
+        let tx = sign_evm(sk, evm_request(node, sk, 1_000_000).data(code));
+        let tx_hash = tx.hash();
+        node.add_tx(tx).unwrap();
+        let block = node.produce_block(true);
+
+        let output = block.call_outputs.get(&tx_hash).unwrap().clone().unwrap();
+        let (address, _): ([u8; 20], Vec<u8>) = borsh::from_slice(&output).unwrap();
+        address
+    }
+
+    fn evm_request(node: &MultivmNode, sk: &str, gas: u64) -> TransactionRequest {
+        let from = address(sk);
+        let nonce = node
+            .account_info(&from.clone().into())
+            .map(|account| account.nonce)
+            .unwrap_or_default();
+        TransactionRequest::new()
+            .from(eth_primitive_types::H160::from(from))
+            .nonce(nonce)
+            .gas(gas)
+            .gas_price(BASE_FEE_PER_GAS)
+            .chain_id(CHAIN_ID)
+    }
+
+    /// Legacy EIP-155 transaction signed by the key
+    fn sign_evm(sk: &str, tx: TransactionRequest) -> SupportedTransaction {
+        let (signature, recovery_id) = signing_key(sk)
+            .sign_prehash_recoverable(tx.sighash().as_bytes())
+            .unwrap();
+        let signature = Signature {
+            r: U256::from_big_endian(&signature.r().to_bytes()),
+            s: U256::from_big_endian(&signature.s().to_bytes()),
+            v: recovery_id.to_byte() as u64 + 35 + CHAIN_ID * 2,
+        };
+
+        let request = EthereumTransactionRequest::new(tx.rlp_signed(&signature).to_vec());
+        SupportedTransaction::Evm(request)
+    }
+
+    fn create_alice(node: &mut MultivmNode) {
+        let request = (
+            Some(MultiVmAccountId::try_from("alice.multivm").unwrap()),
+            address(ALICE_SK),
+        );
+        send(
+            node,
+            "super.multivm",
+            SUPER_ACCOUNT_SK,
+            ContractCall::new("create_account".into(), &request, 100_000_000, 0),
+        )
+        .unwrap();
+    }
+
+    /// Creates `bob.multivm` signed by `ALICE_SECOND_SK` and deploys a contract to it
+    fn create_bob_contract(node: &mut MultivmNode) {
+        let request = (
+            Some(MultiVmAccountId::try_from("bob.multivm").unwrap()),
+            address(ALICE_SECOND_SK),
+        );
+        send(
+            node,
+            "super.multivm",
+            SUPER_ACCOUNT_SK,
+            key_call("create_account", &request),
+        )
+        .unwrap();
+
+        let image_id = meta_contracts::SYSTEM_META_CONTRACT_ID;
+        let tx = TransactionBuilder::new(
+            AccountId::system_meta_contract(),
+            vec![key_call("deploy_contract", &image_id)],
+            account_id("bob.multivm"),
+            &node.latest_block(),
+        )
+        .set_nonce(node.pending_nonce(&account_id("bob.multivm")))
+        .build();
+        let attachments = Attachments {
+            contracts_images: HashMap::from([(
+                image_id,
+                meta_contracts::SYSTEM_META_CONTRACT_ELF.to_vec(),
+            )]),
+        };
+        let tx =
+            SignedTransaction::new_with_attachments(tx, &signing_key(ALICE_SECOND_SK), attachments);
+        submit(node, tx.into()).unwrap();
+    }
+
+    /// Calls the system meta-contract from the contract within the signer transaction,
+    /// nested calls keep the signer of the transaction
+    fn call_from_contract(
+        node: &MultivmNode,
+        contract: &str,
+        signer: &str,
+        call: ContractCall,
+    ) -> ContractResponse {
+        let context = ContractCallContext {
+            contract_id: AccountId::system_meta_contract(),
+            contract_call: call,
+            sender_id: account_id(contract),
+            signer_id: account_id(signer),
+            environment: node.environment(),
+        };
+
+        Executor::new(context, node.db.clone())
+            .execute()
+            .expect("Contract call failed")
+            .commitment
+            .response
+    }
+
+    /// Executor of the contract called by alice, its storage syscalls are invoked directly
+    fn storage_executor(node: &MultivmNode, contract: &str) -> Executor {
+        let context = ContractCallContext {
+            contract_id: account_id(contract),
+            contract_call: key_call("storage", &()),
+            sender_id: account_id("alice.multivm"),
+            signer_id: account_id("alice.multivm"),
+            environment: node.environment(),
+        };
+
+        Executor::new(context, node.db.clone())
+    }
+
+    fn storage_update(key: &str, storage: Vec<u8>) -> SetStorageRequest {
+        SetStorageRequest {
+            key: key.into(),
+            hash: HashImpl::hash_bytes(&storage)
+                .as_bytes()
+                .try_into()
+                .unwrap(),
+            storage,
+        }
+    }
+
+    fn storage_usage(node: &MultivmNode, account_id: &AccountId) -> u64 {
+        node.account_info(account_id).unwrap().storage_usage
+    }
+
+    fn transfer(
+        node: &mut MultivmNode,
+        signer: &str,
+        sk: &str,
+        receiver: &str,
+        amount: u128,
+    ) -> Result<(), String> {
+        send(
+            node,
+            signer,
+            sk,
+            ContractCall::new(
+                "transfer".into(),
+                &(account_id(receiver), amount),
+                100_000_000,
+                0,
+            ),
+        )
+    }
+
+    fn key_call(method: &str, args: &impl BorshSerialize) -> ContractCall {
+        ContractCall::new(method.into(), args, 100_000_000, 0)
+    }
+
+    fn balances_sum(node: &MultivmNode) -> u128 {
+        balance(node, &account_id("super.multivm")) + balance(node, &account_id("alice.multivm"))
+    }
+
     #[test]
     fn test_init_genesis() {
         let mut node = MultivmNode::new("temp_multivm_db".to_string());
@@ -172,4 +857,1128 @@ mod tests {
         let latest_block = node.latest_block();
         assert_eq!(latest_block.height, 1);
     }
+
+    #[test]
+    fn test_total_supply_after_account_creation() {
+        let mut node = genesis_node("temp_multivm_db_account_creation");
+        let genesis_supply = node.total_supply();
+        assert_eq!(genesis_supply, balances_sum(&node));
+
+        create_alice(&mut node);
+
+        assert_eq!(
+            balance(&node, &account_id("alice.multivm")),
+            1_000 * ONE_TOKEN
+        );
+        // Creation fee and gas are paid to the fee collector, nothing is minted or burned
+        assert_eq!(node.total_supply(), genesis_supply);
+        assert_eq!(node.total_supply(), balances_sum(&node));
+    }
+
+    #[test]
+    fn test_total_supply_after_transfers() {
+        let mut node = genesis_node("temp_multivm_db_transfers");
+        create_alice(&mut node);
+        let supply = node.total_supply();
+
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            "super.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap();
+        assert_eq!(node.total_supply(), supply);
+        assert_eq!(node.total_supply(), balances_sum(&node));
+
+        // Self-transfer changes nothing, system calls don't charge gas
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            "alice.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap();
+        assert_eq!(balance(&node, &account_id("alice.multivm")), alice_balance);
+        assert_eq!(node.total_supply(), supply);
+        assert_eq!(node.total_supply(), balances_sum(&node));
+
+        // Failed transfer doesn't mint the missing amount
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            "super.multivm",
+            u128::MAX,
+        )
+        .unwrap_err();
+        assert_eq!(node.total_supply(), supply);
+        assert_eq!(node.total_supply(), balances_sum(&node));
+    }
+
+    #[test]
+    fn test_removed_key_loses_access() {
+        let mut node = genesis_node("temp_multivm_db_remove_key");
+        create_alice(&mut node);
+
+        let second_key = AccessKey::full_access(address(ALICE_SECOND_SK));
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("add_key", &second_key),
+        )
+        .unwrap();
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SECOND_SK,
+            "super.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap();
+
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SECOND_SK,
+            key_call("remove_key", &address(ALICE_SK)),
+        )
+        .unwrap();
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            "super.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap_err();
+        // Alias address of the removed key can't sign EVM transactions either
+        send_evm(&mut node, ALICE_SK, address(SUPER_ACCOUNT_SK), ONE_TOKEN).unwrap_err();
+
+        // The last full access key stays
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SECOND_SK,
+            key_call("remove_key", &address(ALICE_SECOND_SK)),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_rotated_key_loses_access() {
+        let mut node = genesis_node("temp_multivm_db_rotate_key");
+        create_alice(&mut node);
+        send_evm(&mut node, ALICE_SK, address(SUPER_ACCOUNT_SK), ONE_TOKEN).unwrap();
+
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("rotate_key", &(address(ALICE_SK), address(ALICE_SECOND_SK))),
+        )
+        .unwrap();
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            "super.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap_err();
+        send_evm(&mut node, ALICE_SK, address(SUPER_ACCOUNT_SK), ONE_TOKEN).unwrap_err();
+        assert_eq!(balance(&node, &account_id("alice.multivm")), alice_balance);
+
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SECOND_SK,
+            "super.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_function_call_key_limits() {
+        let mut node = genesis_node("temp_multivm_db_function_call_key");
+        create_alice(&mut node);
+
+        let token_id = account_id("token.multivm");
+        let limited_key = AccessKey {
+            address: address(ALICE_SECOND_SK),
+            permission: AccessKeyPermission::FunctionCall {
+                contract_id: token_id.clone(),
+                methods: vec!["ft_transfer".to_string()],
+                gas_allowance: 150_000_000,
+            },
+        };
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("add_key", &limited_key),
+        )
+        .unwrap();
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+
+        // Other contracts, including the system one managing keys
+        let error = transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SECOND_SK,
+            "super.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap_err();
+        assert!(error.contains("Access key doesn't allow"), "{}", error);
+        let error = send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SECOND_SK,
+            key_call("remove_key", &address(ALICE_SK)),
+        )
+        .unwrap_err();
+        assert!(error.contains("Access key doesn't allow"), "{}", error);
+
+        let mut token_call = |call: ContractCall| {
+            send_to(
+                &mut node,
+                "alice.multivm",
+                ALICE_SECOND_SK,
+                token_id.clone(),
+                call,
+            )
+            .unwrap_err()
+        };
+        let error = token_call(ContractCall::new("ft_mint".into(), &(), 100_000_000, 0));
+        assert!(error.contains("Access key doesn't allow"), "{}", error);
+        let error = token_call(ContractCall::new("ft_transfer".into(), &(), 100_000_000, 1));
+        assert!(
+            error.contains("Access key doesn't allow deposits"),
+            "{}",
+            error
+        );
+        let error = token_call(ContractCall::new("ft_transfer".into(), &(), 200_000_000, 0));
+        assert!(error.contains("gas allowance exceeded"), "{}", error);
+
+        assert_eq!(balance(&node, &account_id("alice.multivm")), alice_balance);
+
+        // Limited keys can't add keys to the account
+        let key = AccessKey {
+            address: address(SUPER_ACCOUNT_SK),
+            permission: AccessKeyPermission::FunctionCall {
+                contract_id: AccountId::system_meta_contract(),
+                methods: vec![],
+                gas_allowance: u64::MAX,
+            },
+        };
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("add_key", &key),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_contract_cant_manage_signer_keys() {
+        let mut node = genesis_node("temp_multivm_db_contract_keys");
+        create_alice(&mut node);
+        create_bob_contract(&mut node);
+        let alice_id = account_id("alice.multivm");
+        let keys = node.access_keys(&alice_id);
+
+        let key = AccessKey::full_access(address(ALICE_SECOND_SK));
+        let error = call_from_contract(
+            &node,
+            "bob.multivm",
+            "alice.multivm",
+            key_call("add_key", &key),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:?}", error).contains("Only the signer"),
+            "{:?}",
+            error
+        );
+        call_from_contract(
+            &node,
+            "bob.multivm",
+            "alice.multivm",
+            key_call("remove_key", &address(ALICE_SK)),
+        )
+        .unwrap_err();
+        call_from_contract(
+            &node,
+            "bob.multivm",
+            "alice.multivm",
+            key_call("rotate_key", &(address(ALICE_SK), address(ALICE_SECOND_SK))),
+        )
+        .unwrap_err();
+        assert_eq!(node.access_keys(&alice_id), keys);
+
+        let multisig = Multisig {
+            keys: vec![address(ALICE_SECOND_SK)],
+            threshold: 1,
+        };
+        call_from_contract(
+            &node,
+            "bob.multivm",
+            "alice.multivm",
+            key_call("set_multisig", &multisig),
+        )
+        .unwrap_err();
+        assert!(node.multisig(&alice_id).is_none());
+
+        // Called by the transaction itself
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("add_key", &key),
+        )
+        .unwrap();
+        assert_eq!(node.access_keys(&alice_id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_contract_cant_change_signer_contract() {
+        let mut node = genesis_node("temp_multivm_db_contract_upgrade_guard");
+        create_alice(&mut node);
+        create_bob_contract(&mut node);
+        let bob_id = account_id("bob.multivm");
+
+        // Another contract called by a transaction of bob
+        let upgrade = ([1u32; 8], None::<ContractCall>);
+        let error = call_from_contract(
+            &node,
+            "token.multivm",
+            "bob.multivm",
+            key_call("upgrade_contract", &upgrade),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:?}", error).contains("Only the signer"),
+            "{:?}",
+            error
+        );
+        call_from_contract(
+            &node,
+            "token.multivm",
+            "bob.multivm",
+            key_call("make_immutable", &()),
+        )
+        .unwrap_err();
+        let abi = vec!["function get() returns (uint64)".to_string()];
+        let error = call_from_contract(
+            &node,
+            "token.multivm",
+            "bob.multivm",
+            key_call("declare_abi", &abi),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:?}", error).contains("Only the signer"),
+            "{:?}",
+            error
+        );
+
+        let is_immutable = node
+            .system_view("is_immutable".to_string(), &bob_id)
+            .unwrap();
+        assert!(!borsh::from_slice::<bool>(&is_immutable).unwrap());
+        assert_eq!(
+            code_store::contract_image_id(&node.db, &bob_id),
+            Some(meta_contracts::SYSTEM_META_CONTRACT_ID)
+        );
+    }
+
+    #[test]
+    fn test_multisig_threshold() {
+        let mut node = genesis_node("temp_multivm_db_multisig");
+        create_alice(&mut node);
+
+        let multisig = Multisig {
+            keys: vec![address(ALICE_SK), address(ALICE_SECOND_SK)],
+            threshold: 2,
+        };
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("set_multisig", &multisig),
+        )
+        .unwrap();
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+
+        // Threshold - 1 signatures, also repeated by the same key
+        transfer(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            "super.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap_err();
+        let call = ContractCall::new(
+            "transfer".into(),
+            &(account_id("super.multivm"), ONE_TOKEN),
+            100_000_000,
+            0,
+        );
+        let tx = TransactionBuilder::new(
+            AccountId::system_meta_contract(),
+            vec![call.clone()],
+            account_id("alice.multivm"),
+            &node.latest_block(),
+        )
+        .set_nonce(node.pending_nonce(&account_id("alice.multivm")))
+        .build();
+        let tx = SignedTransaction::new(tx, &signing_key(ALICE_SK)).cosign(&signing_key(ALICE_SK));
+        submit(&mut node, tx.into()).unwrap_err();
+        // The alias key alone can't bypass the threshold through EVM
+        send_evm(&mut node, ALICE_SK, address(SUPER_ACCOUNT_SK), ONE_TOKEN).unwrap_err();
+        assert_eq!(balance(&node, &account_id("alice.multivm")), alice_balance);
+
+        let tx = TransactionBuilder::new(
+            AccountId::system_meta_contract(),
+            vec![call],
+            account_id("alice.multivm"),
+            &node.latest_block(),
+        )
+        .set_nonce(node.pending_nonce(&account_id("alice.multivm")))
+        .build();
+        let tx = SignedTransaction::new(tx, &signing_key(ALICE_SK))
+            .cosign(&signing_key(ALICE_SECOND_SK));
+        submit(&mut node, tx.into()).unwrap();
+    }
+
+    #[test]
+    fn test_transaction_nonce() {
+        let mut node = genesis_node("temp_multivm_db_nonce");
+        create_alice(&mut node);
+
+        let call = ContractCall::new(
+            "transfer".into(),
+            &(account_id("super.multivm"), ONE_TOKEN),
+            100_000_000,
+            0,
+        );
+        let nonce = node.pending_nonce(&account_id("alice.multivm"));
+        let signed_tx = |nonce: u64, node: &MultivmNode| {
+            let tx = TransactionBuilder::new(
+                AccountId::system_meta_contract(),
+                vec![call.clone()],
+                account_id("alice.multivm"),
+                &node.latest_block(),
+            )
+            .set_nonce(nonce)
+            .build();
+            SignedTransaction::new(tx, &signing_key(ALICE_SK))
+        };
+
+        // Nonces from the future aren't executed
+        let tx = signed_tx(nonce + 1, &node);
+        let error = submit(&mut node, tx.into()).unwrap_err();
+        assert!(error.contains("Invalid nonce"), "{}", error);
+
+        let tx = signed_tx(nonce, &node);
+        submit(&mut node, tx.clone().into()).unwrap();
+        assert_eq!(
+            node.account_info(&account_id("alice.multivm"))
+                .unwrap()
+                .nonce,
+            nonce + 1
+        );
+
+        // Replayed transaction is rejected by the pool
+        let error = submit(&mut node, tx.into()).unwrap_err();
+        assert!(error.contains("Nonce too low"), "{}", error);
+    }
+
+    #[test]
+    fn test_failed_transaction_reverts_contract_code() {
+        let mut node = genesis_node("temp_multivm_db_code_revert");
+        create_alice(&mut node);
+
+        // Image is deployed by the first call, the second one fails, as a failed migration would
+        let image_id = meta_contracts::SYSTEM_META_CONTRACT_ID;
+        let calls = vec![
+            ContractCall::new("deploy_contract".into(), &image_id, 100_000_000, 0),
+            ContractCall::new(
+                "transfer".into(),
+                &(account_id("super.multivm"), u128::MAX),
+                100_000_000,
+                0,
+            ),
+        ];
+        let tx = TransactionBuilder::new(
+            AccountId::system_meta_contract(),
+            calls,
+            account_id("alice.multivm"),
+            &node.latest_block(),
+        )
+        .set_nonce(node.pending_nonce(&account_id("alice.multivm")))
+        .build();
+        let attachments = Attachments {
+            contracts_images: HashMap::from([(
+                image_id,
+                meta_contracts::SYSTEM_META_CONTRACT_ELF.to_vec(),
+            )]),
+        };
+        let tx = SignedTransaction::new_with_attachments(tx, &signing_key(ALICE_SK), attachments);
+        submit(&mut node, tx.into()).unwrap_err();
+
+        assert!(code_store::contract_image_id(&node.db, &account_id("alice.multivm")).is_none());
+        assert!(node
+            .account_info(&account_id("alice.multivm"))
+            .unwrap()
+            .executable
+            .is_none());
+    }
+
+    #[test]
+    fn test_evm_transfer_account_creation() {
+        let mut node = genesis_node("temp_multivm_db_evm_account_creation");
+        create_alice(&mut node);
+        let supply = node.total_supply();
+        let receiver = address(ALICE_SECOND_SK);
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+        let transfer_fee = 21_000 * BASE_FEE_PER_GAS;
+
+        // Touching an address doesn't create an account
+        send_evm(&mut node, ALICE_SK, receiver.clone(), 0).unwrap();
+        assert!(node.account_info(&receiver.clone().into()).is_none());
+        assert_eq!(
+            balance(&node, &account_id("alice.multivm")),
+            alice_balance - transfer_fee
+        );
+
+        // Value transfer creates it for the creation fee
+        send_evm(&mut node, ALICE_SK, receiver.clone(), ONE_TOKEN).unwrap();
+        assert_eq!(balance(&node, &receiver.into()), ONE_TOKEN);
+        assert_eq!(
+            balance(&node, &account_id("alice.multivm")),
+            alice_balance - 2 * transfer_fee - 2 * ONE_TOKEN
+        );
+        assert_eq!(node.total_supply(), supply);
+    }
+
+    #[test]
+    fn test_evm_nested_create() {
+        let mut node = genesis_node("temp_multivm_db_evm_nested_create");
+        create_alice(&mut node);
+        // Child init code returning `0x2a` as the runtime code
+        let child_code = hex::decode("602a60005360016000f3").unwrap();
+        // Factory init code deploying the child with CREATE and with CREATE2 under salt 1
+        let factory_code =
+            hex::decode("69602a60005360016000f3600052600a60166000f0506001600a60166000f55000")
+                .unwrap();
+
+        let factory = deploy_evm(&mut node, ALICE_SK, factory_code);
+        assert_ne!(factory, [0; 20]);
+        let mut salt = [0u8; 32];
+        salt[31] = 1;
+        let created = [
+            ethers_core::utils::get_contract_address(factory, 1u64),
+            ethers_core::utils::get_create2_address(factory, salt.to_vec(), child_code),
+        ];
+        for address in created {
+            let address = EvmAddress::from(address.to_fixed_bytes());
+            assert!(node.account_info(&address.clone().into()).is_some());
+            let code = node.system_view("evm_code".to_string(), &address).unwrap();
+            assert_eq!(borsh::from_slice::<Vec<u8>>(&code).unwrap(), vec![0x2a]);
+        }
+
+        // Failed deployment reports no address
+        assert_eq!(deploy_evm(&mut node, ALICE_SK, vec![0xfe]), [0; 20]);
+    }
+
+    #[test]
+    fn test_failed_contract_call_refunds_deposit() {
+        let mut node = genesis_node("temp_multivm_db_deposit_refund");
+        create_alice(&mut node);
+        create_bob_contract(&mut node);
+
+        // The contract can't handle the call, only the gas is paid
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+        let bob_balance = balance(&node, &account_id("bob.multivm"));
+        let gas = 100_000_000;
+        let _ = send_to(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            account_id("bob.multivm"),
+            ContractCall::new("unknown_method".into(), &(), gas, ONE_TOKEN),
+        );
+        assert_eq!(balance(&node, &account_id("bob.multivm")), bob_balance);
+        assert_eq!(
+            balance(&node, &account_id("alice.multivm")),
+            alice_balance - gas as u128
+        );
+    }
+
+    #[test]
+    fn test_evm_gas_limit() {
+        let mut node = genesis_node("temp_multivm_db_evm_gas_limit");
+        create_alice(&mut node);
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+
+        let error = send_evm_with_gas(
+            &mut node,
+            ALICE_SK,
+            address(SUPER_ACCOUNT_SK),
+            ONE_TOKEN,
+            20_000,
+        )
+        .unwrap_err();
+        assert!(error.contains("lower than intrinsic gas"), "{}", error);
+
+        // The whole gas limit has to be covered by the balance, even if it is not used
+        let gas = (alice_balance / BASE_FEE_PER_GAS) as u64 + 1;
+        let error =
+            send_evm_with_gas(&mut node, ALICE_SK, address(SUPER_ACCOUNT_SK), 0, gas).unwrap_err();
+        assert!(error.contains("Insufficient funds"), "{}", error);
+        assert_eq!(balance(&node, &account_id("alice.multivm")), alice_balance);
+    }
+
+    #[test]
+    fn test_legacy_contract_code_migration() {
+        let db_path = "temp_multivm_db_code_migration";
+        let node = genesis_node(db_path);
+        let alice_id = account_id("alice.multivm");
+        // Contracts deployed before the code store kept the ELF by the account
+        node.db
+            .insert(
+                format!("contracts_code.{}", alice_id),
+                meta_contracts::SYSTEM_META_CONTRACT_ELF,
+            )
+            .unwrap();
+        drop(node);
+
+        let node = MultivmNode::new(db_path.to_string());
+        assert_eq!(
+            code_store::contract_image_id(&node.db, &alice_id),
+            Some(meta_contracts::SYSTEM_META_CONTRACT_ID)
+        );
+        assert_eq!(
+            code_store::contract_code(&node.db, &alice_id).as_deref(),
+            Some(meta_contracts::SYSTEM_META_CONTRACT_ELF)
+        );
+    }
+
+    #[test]
+    fn test_storage_pre_history_value() {
+        let node = genesis_node("temp_multivm_db_storage_pre_history");
+        let alice_id = account_id("alice.multivm");
+        // Values committed before history was recorded
+        node.db
+            .insert(format!("committed_storage.{}.slot", alice_id), vec![1])
+            .unwrap();
+        node.db
+            .insert(format!("committed_storage.{}.removed", alice_id), vec![2])
+            .unwrap();
+
+        storage::insert(&node.db, &alice_id, "slot", 5, vec![3]);
+        storage::remove(&node.db, &alice_id, "removed", 5);
+
+        assert_eq!(
+            storage::get_at(&node.db, &alice_id, "slot", 4),
+            Some(vec![1])
+        );
+        assert_eq!(
+            storage::get_at(&node.db, &alice_id, "slot", 5),
+            Some(vec![3])
+        );
+        assert_eq!(
+            storage::get_at(&node.db, &alice_id, "removed", 4),
+            Some(vec![2])
+        );
+        assert_eq!(storage::get_at(&node.db, &alice_id, "removed", 5), None);
+        assert_eq!(storage::get_at(&node.db, &alice_id, "missing", 5), None);
+    }
+
+    #[test]
+    fn test_block_by_transaction() {
+        let db_path = "temp_multivm_db_transaction_index";
+        let mut node = genesis_node(db_path);
+        create_alice(&mut node);
+        transfer(
+            &mut node,
+            "super.multivm",
+            SUPER_ACCOUNT_SK,
+            "alice.multivm",
+            1,
+        )
+        .unwrap();
+
+        let block = node.latest_block();
+        let tx_hash = block.txs[0].hash();
+        assert_eq!(
+            node.block_by_transaction(&tx_hash)
+                .map(|block| block.height),
+            Some(block.height)
+        );
+        assert!(node.block_by_transaction(&[0; 32]).is_none());
+        assert_eq!(
+            node.block_by_hash(&block.hash).map(|block| block.height),
+            Some(block.height)
+        );
+
+        // Blocks stored before the index was kept are indexed on start
+        node.db.remove(b"blocks_indexed").unwrap();
+        node.db
+            .remove(MultivmNode::transaction_height_key(&tx_hash))
+            .unwrap();
+        drop(node);
+
+        let node = MultivmNode::new(db_path.to_string());
+        assert_eq!(
+            node.block_by_transaction(&tx_hash)
+                .map(|block| block.height),
+            Some(block.height)
+        );
+    }
+
+    fn stake_call(amount: u128) -> ContractCall {
+        let public_key = signing_key(ALICE_SECOND_SK)
+            .verifying_key()
+            .to_sec1_bytes()
+            .to_vec();
+        key_call("stake", &(amount, public_key, 500u16))
+    }
+
+    fn unbonding(node: &MultivmNode, account_id: &AccountId) -> Vec<Unbonding> {
+        let bytes = node
+            .system_view("unbonding".to_string(), account_id)
+            .unwrap();
+        borsh::from_slice::<Option<Vec<Unbonding>>>(&bytes)
+            .unwrap()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_validators_epoch_snapshot() {
+        let mut node = genesis_node("temp_multivm_db_validators_snapshot");
+        create_alice(&mut node);
+        // The first block snapshots epoch 0, before anybody stakes
+        assert_eq!(node.epoch_validators(0), Some(vec![]));
+
+        let supply = node.total_supply();
+        let alice_balance = balance(&node, &account_id("alice.multivm"));
+        send(&mut node, "alice.multivm", ALICE_SK, stake_call(ONE_TOKEN)).unwrap();
+        assert_eq!(
+            balance(&node, &account_id("alice.multivm")),
+            alice_balance - ONE_TOKEN
+        );
+        assert_eq!(node.total_supply(), supply);
+
+        let validators = node.validators();
+        assert_eq!(validators.len(), 1);
+        assert_eq!(validators[0].account_id, account_id("alice.multivm"));
+        assert_eq!(validators[0].stake, ONE_TOKEN);
+        // Staking changes the next epoch only
+        assert_eq!(node.epoch_validators(0), Some(vec![]));
+        assert_eq!(node.block_producer(node.latest_block().height), None);
+
+        while node.latest_block().height < EPOCH_LENGTH - 1 {
+            node.produce_block(true);
+        }
+        assert_eq!(node.epoch_validators(1), None);
+
+        let block = node.produce_block(true);
+        assert_eq!(block.height, EPOCH_LENGTH);
+        assert_eq!(node.epoch_validators(1), Some(validators));
+        assert_eq!(block.producer, Some(account_id("alice.multivm")));
+
+        // Snapshot belongs to the first block of the epoch, not to the previous one
+        let at_height = |height| {
+            let bytes = node
+                .system_view_at("epoch_validators".to_string(), &1u64, Some(height))
+                .unwrap();
+            borsh::from_slice::<Option<Vec<Validator>>>(&bytes).unwrap()
+        };
+        assert!(at_height(EPOCH_LENGTH - 1).is_none());
+        assert!(at_height(EPOCH_LENGTH).is_some());
+    }
+
+    #[test]
+    fn test_unstake_unbonding() {
+        let mut node = genesis_node("temp_multivm_db_unstake");
+        create_alice(&mut node);
+        let supply = node.total_supply();
+        let alice_id = account_id("alice.multivm");
+
+        send(&mut node, "alice.multivm", ALICE_SK, stake_call(ONE_TOKEN)).unwrap();
+        let error = send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("unstake", &0u128),
+        )
+        .unwrap_err();
+        assert!(error.contains("Nothing to unstake"), "{}", error);
+        send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("unstake", &ONE_TOKEN),
+        )
+        .unwrap();
+        assert!(node.validators().is_empty());
+
+        let unbonding = unbonding(&node, &alice_id);
+        assert_eq!(unbonding.len(), 1);
+        assert_eq!(unbonding[0].amount, ONE_TOKEN);
+        assert_eq!(
+            unbonding[0].unlock_height,
+            node.latest_block().height + UNBONDING_PERIOD
+        );
+        // Staked and unbonding tokens are still a part of the supply
+        assert_eq!(node.total_supply(), supply);
+
+        let error = send(
+            &mut node,
+            "alice.multivm",
+            ALICE_SK,
+            key_call("withdraw", &()),
+        )
+        .unwrap_err();
+        assert!(error.contains("Nothing to withdraw"), "{}", error);
+    }
+
+    fn abi(signature: &str) -> ethers_core::abi::Abi {
+        solidity_abi::parse(&[signature.to_string()])
+    }
+
+    /// Borsh args of the ABI encoded call of the only function
+    fn abi_args(abi: &ethers_core::abi::Abi, tokens: &[Token]) -> Vec<u8> {
+        let function = abi.functions().next().unwrap();
+        let data = function.encode_input(tokens).unwrap();
+        solidity_abi::decode_call(abi, &data).1
+    }
+
+    #[test]
+    fn test_solidity_abi_conversion() {
+        let abi = abi("function transfer(address,uint128,bool,string,int8) returns (uint64,int16)");
+        let receiver = [7u8; 20];
+        let args = abi_args(
+            &abi,
+            &[
+                Token::Address(receiver.into()),
+                Token::Uint(5.into()),
+                Token::Bool(true),
+                Token::String("memo".into()),
+                Token::Int(U256::MAX),
+            ],
+        );
+        assert_eq!(
+            args,
+            borsh::to_vec(&(receiver, 5u128, true, "memo".to_string(), -1i8)).unwrap()
+        );
+
+        let output = borsh::to_vec(&(7u64, -2i16)).unwrap();
+        assert_eq!(
+            solidity_abi::encode_output(abi.functions().next().unwrap(), &output),
+            ethers_core::abi::encode(&[Token::Uint(7.into()), Token::Int(U256::MAX - 1)])
+        );
+    }
+
+    #[test]
+    fn test_solidity_abi_integer_range() {
+        let uint8 = abi("function f(uint8)");
+        assert_eq!(abi_args(&uint8, &[Token::Uint(255.into())]), vec![255]);
+        let int8 = abi("function f(int8)");
+        assert_eq!(abi_args(&int8, &[Token::Int(127.into())]), vec![127]);
+        assert_eq!(abi_args(&int8, &[Token::Int(U256::MAX - 127)]), vec![0x80]);
+
+        // Values not fitting the type are rejected instead of truncated
+        let out_of_range = [
+            (&uint8, Token::Uint(256.into())),
+            (&int8, Token::Int(128.into())),
+            (&int8, Token::Int(U256::MAX - 128)),
+        ];
+        for (abi, token) in out_of_range {
+            let result = std::panic::catch_unwind(|| abi_args(abi, &[token.clone()]));
+            assert!(result.is_err(), "{:?} is accepted", token);
+        }
+    }
+
+    #[test]
+    fn test_storage_staking() {
+        let mut node = genesis_node("temp_multivm_db_storage_staking");
+        create_alice(&mut node);
+        create_bob_contract(&mut node);
+        let bob_id = account_id("bob.multivm");
+
+        // Leave bob about enough balance for 100 bytes of storage
+        let before = balance(&node, &bob_id);
+        transfer(
+            &mut node,
+            "bob.multivm",
+            ALICE_SECOND_SK,
+            "alice.multivm",
+            ONE_TOKEN,
+        )
+        .unwrap();
+        let fee = before - ONE_TOKEN - balance(&node, &bob_id);
+        let amount = balance(&node, &bob_id) - fee - 100 * STORAGE_PRICE_PER_BYTE;
+        transfer(
+            &mut node,
+            "bob.multivm",
+            ALICE_SECOND_SK,
+            "alice.multivm",
+            amount,
+        )
+        .unwrap();
+        let bob_balance = balance(&node, &bob_id);
+
+        // 3 bytes of key and 40 bytes of value lock the balance
+        let executor = storage_executor(&node, "bob.multivm");
+        let request = storage_update("one", vec![1; 40]);
+        (executor.callback_on_set_storage())(borsh::to_vec(&request).unwrap().into()).unwrap();
+        assert_eq!(storage_usage(&node, &bob_id), 43);
+        assert_eq!(balance(&node, &bob_id), bob_balance);
+        assert_eq!(storage::get(&node.db, &bob_id, "one"), Some(vec![1; 40]));
+
+        // The balance doesn't cover 43 + 203 bytes, nothing is written
+        let request = CommitStorageRequest {
+            updates: vec![storage_update("two", vec![2; 200])],
+        };
+        let response =
+            (executor.callback_on_commit_storage())(borsh::to_vec(&request).unwrap().into())
+                .unwrap();
+        let commitment: Option<Commitment> = BorshDeserialize::try_from_slice(&response).unwrap();
+        assert!(commitment.unwrap().response.is_err());
+        assert_eq!(storage_usage(&node, &bob_id), 43);
+        assert_eq!(storage::get(&node.db, &bob_id, "two"), None);
+
+        // Only the last update of the key is charged
+        let request = CommitStorageRequest {
+            updates: vec![
+                storage_update("one", vec![3; 200]),
+                storage_update("one", vec![4; 10]),
+            ],
+        };
+        (executor.callback_on_commit_storage())(borsh::to_vec(&request).unwrap().into()).unwrap();
+        assert_eq!(storage_usage(&node, &bob_id), 13);
+        assert_eq!(storage::get(&node.db, &bob_id, "one"), Some(vec![4; 10]));
+
+        // Freed bytes unlock the balance
+        let request = DeleteStorageRequest { key: "one".into() };
+        (executor.callback_on_delete_storage())(borsh::to_vec(&request).unwrap().into()).unwrap();
+        assert_eq!(storage_usage(&node, &bob_id), 0);
+        assert_eq!(storage::get(&node.db, &bob_id, "one"), None);
+        assert_eq!(balance(&node, &bob_id), bob_balance);
+    }
+
+    #[test]
+    fn test_create_sub_contract() {
+        let mut node = genesis_node("temp_multivm_db_sub_contract");
+        create_alice(&mut node);
+        create_bob_contract(&mut node);
+        transfer(
+            &mut node,
+            "super.multivm",
+            SUPER_ACCOUNT_SK,
+            "bob.multivm",
+            10 * ONE_TOKEN,
+        )
+        .unwrap();
+        let image_id = meta_contracts::SYSTEM_META_CONTRACT_ID;
+
+        // Only contracts create sub-contracts, and only under their own name
+        let error = call_from_contract(
+            &node,
+            "alice.multivm",
+            "alice.multivm",
+            key_call(
+                "create_sub_contract",
+                &(account_id("sub.alice.multivm"), image_id),
+            ),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:?}", error).contains("Only MultiVM contracts"),
+            "{:?}",
+            error
+        );
+        let error = call_from_contract(
+            &node,
+            "bob.multivm",
+            "alice.multivm",
+            key_call(
+                "create_sub_contract",
+                &(account_id("sub.alice.multivm"), image_id),
+            ),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:?}", error).contains("is not a sub-account"),
+            "{:?}",
+            error
+        );
+
+        // The address of the sub-contract is known in advance, tokens sent there aren't lost
+        let hash = ethers_core::utils::keccak256("token.bob.multivm".as_bytes());
+        let token_address: [u8; 20] = hash[12..].try_into().unwrap();
+        let token_address = EvmAddress::from(token_address);
+        send_evm(&mut node, ALICE_SK, token_address.clone(), 1).unwrap();
+        let squatted = balance(&node, &token_address.clone().into());
+        assert_eq!(squatted, 1);
+
+        let bob_id = account_id("bob.multivm");
+        let super_id = account_id("super.multivm");
+        let bob_balance = balance(&node, &bob_id);
+        let super_balance = balance(&node, &super_id);
+        call_from_contract(
+            &node,
+            "bob.multivm",
+            "alice.multivm",
+            key_call(
+                "create_sub_contract",
+                &(account_id("token.bob.multivm"), image_id),
+            ),
+        )
+        .unwrap();
+
+        // The factory pays the fee to the collector and funds the sub-contract
+        let token_id = account_id("token.bob.multivm");
+        let token = node.account_info(&token_id).unwrap();
+        assert_eq!(token.evm_address, token_address);
+        assert!(token.executable.is_some());
+        assert_eq!(token.balance, squatted + 1_000 * ONE_TOKEN);
+        assert_eq!(
+            balance(&node, &bob_id),
+            bob_balance - ONE_TOKEN - 1_000 * ONE_TOKEN
+        );
+        assert_eq!(balance(&node, &super_id), super_balance + ONE_TOKEN);
+        assert!(node.access_keys(&token_id).unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn test_delegated_transaction() {
+        let mut node = genesis_node("temp_multivm_db_delegated");
+        create_alice(&mut node);
+        let request = (
+            Some(MultiVmAccountId::try_from("carol.multivm").unwrap()),
+            address(ALICE_SECOND_SK),
+        );
+        send(
+            &mut node,
+            "super.multivm",
+            SUPER_ACCOUNT_SK,
+            key_call("create_account", &request),
+        )
+        .unwrap();
+        let alice_id = account_id("alice.multivm");
+        let carol_id = account_id("carol.multivm");
+        let super_id = account_id("super.multivm");
+
+        // Alice signs the transfer, carol relays it
+        let tx = TransactionBuilder::new(
+            AccountId::system_meta_contract(),
+            vec![ContractCall::new(
+                "transfer".into(),
+                &(super_id.clone(), ONE_TOKEN),
+                100_000_000,
+                0,
+            )],
+            alice_id.clone(),
+            &node.latest_block(),
+        )
+        .set_nonce(node.pending_nonce(&alice_id))
+        .build();
+        let signed_tx = SignedTransaction::new(tx, &signing_key(ALICE_SK));
+        let delegated_tx = DelegatedTransaction::new(
+            signed_tx.clone(),
+            carol_id.clone(),
+            &signing_key(ALICE_SECOND_SK),
+        );
+
+        let alice_balance = balance(&node, &alice_id);
+        let carol_balance = balance(&node, &carol_id);
+        let alice_nonce = node.account_info(&alice_id).unwrap().nonce;
+        let carol_nonce = node.account_info(&carol_id).unwrap().nonce;
+        submit(
+            &mut node,
+            SupportedTransaction::Delegated(delegated_tx.clone()),
+        )
+        .unwrap();
+
+        // The transfer is made by alice, the relayer pays gas only
+        assert_eq!(balance(&node, &alice_id), alice_balance - ONE_TOKEN);
+        assert!(balance(&node, &carol_id) < carol_balance);
+        assert_eq!(node.account_info(&alice_id).unwrap().nonce, alice_nonce + 1);
+        assert_eq!(node.account_info(&carol_id).unwrap().nonce, carol_nonce);
+
+        // The user nonce is spent, the same transaction can't be relayed again
+        let error = submit(&mut node, SupportedTransaction::Delegated(delegated_tx)).unwrap_err();
+        assert!(error.contains("Invalid nonce"), "{}", error);
+        assert_eq!(balance(&node, &alice_id), alice_balance - ONE_TOKEN);
+
+        // The relayer signature covers the relayer, it can't be replaced
+        let mut delegated_tx =
+            DelegatedTransaction::new(signed_tx, carol_id, &signing_key(ALICE_SECOND_SK));
+        delegated_tx.relayer_id = super_id;
+        let error = submit(&mut node, SupportedTransaction::Delegated(delegated_tx)).unwrap_err();
+        assert!(error.contains("Full access key"), "{}", error);
+    }
+
+    #[test]
+    fn test_evm_multivm_call_precompile() {
+        let mut node = genesis_node("temp_multivm_db_multivm_call_precompile");
+        create_alice(&mut node);
+        create_bob_contract(&mut node);
+        // Runtime code passing the call data to the precompile, then stopping or reverting
+        let call_precompile = "3660006000376000600036600060006104005af150";
+        let forwarder = deploy_evm(
+            &mut node,
+            ALICE_SK,
+            hex::decode(format!("601680600b6000396000f3{}00", call_precompile)).unwrap(),
+        );
+        let reverter = deploy_evm(
+            &mut node,
+            ALICE_SK,
+            hex::decode(format!(
+                "601a80600b6000396000f3{}60006000fd",
+                call_precompile
+            ))
+            .unwrap(),
+        );
+
+        // Cross-contract calls made by the transaction to the contract
+        let calls = |node: &MultivmNode, contract: [u8; 20], contract_id: &str| {
+            let input = ethers_core::abi::encode(&[
+                Token::String(contract_id.to_string()),
+                Token::String("ping".to_string()),
+                Token::Bytes(vec![]),
+            ]);
+            let tx = sign_evm(
+                ALICE_SK,
+                evm_request(node, ALICE_SK, 1_000_000)
+                    .to(eth_primitive_types::H160::from(contract))
+                    .data(input),
+            );
+            let outcome =
+                Bootstraper::new(node.db.clone(), tx.clone(), tx.signer(), node.environment())
+                    .bootstrap();
+            assert!(outcome.commitment.response.is_ok());
+            outcome.commitment.cross_calls_hashes.len()
+        };
+
+        assert_eq!(calls(&node, forwarder, "bob.multivm"), 1);
+        // Calls of the reverted frame are dropped with it
+        assert_eq!(calls(&node, reverter, "bob.multivm"), 0);
+        // The system meta-contract can't be called on its own behalf
+        assert_eq!(calls(&node, forwarder, "multivm"), 0);
+    }
 }
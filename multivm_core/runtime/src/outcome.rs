@@ -1,10 +1,28 @@
-use multivm_primitives::Commitment;
+use multivm_primitives::{AccountId, CallTrace, Commitment, Digest, EvmLog, StorageKey};
 
 pub struct ExecutionOutcome {
     pub session_info: risc0_zkvm::SessionInfo,
     pub commitment: Commitment,
     pub gas_used: u64,
     pub cross_calls_outcomes: Vec<ExecutionOutcome>,
+    pub call_trace: CallTrace,
+}
+
+/// Call data and side effects observed by the host during the call execution
+#[derive(Default)]
+pub struct CallRecord {
+    pub storage_reads: Vec<StorageKey>,
+    pub storage_writes: Vec<StorageKey>,
+    pub logs: Vec<String>,
+}
+
+/// Describes the executed call for the call tree
+pub struct CallInfo {
+    pub contract_id: AccountId,
+    pub method: String,
+    pub args_hash: Digest,
+    pub caller: AccountId,
+    pub gas: u64,
 }
 
 impl ExecutionOutcome {
@@ -12,15 +30,58 @@ impl ExecutionOutcome {
         session_info: risc0_zkvm::SessionInfo,
         gas_used: u64,
         cross_calls_outcomes: Vec<ExecutionOutcome>,
+        call: CallInfo,
+        record: CallRecord,
     ) -> Self {
         let commitment = Commitment::try_from_bytes(session_info.journal.bytes.clone())
             .expect("Corrupted journal");
+        let call_trace = CallTrace {
+            contract_id: call.contract_id,
+            method: call.method,
+            args_hash: call.args_hash,
+            caller: call.caller,
+            gas: call.gas,
+            cycles: session_info
+                .segments
+                .iter()
+                .map(|segment| segment.cycles as u64)
+                .sum(),
+            response: commitment.response.clone(),
+            storage_reads: record.storage_reads,
+            storage_writes: record.storage_writes,
+            logs: record.logs,
+            calls: cross_calls_outcomes
+                .iter()
+                .map(|outcome| outcome.call_trace.clone())
+                .collect(),
+        };
         Self {
             session_info,
             commitment,
             gas_used,
             cross_calls_outcomes,
+            call_trace,
+        }
+    }
+
+    /// Returns zkVM cycles used by the call and its cross-calls
+    pub fn total_cycles(&self) -> u64 {
+        self.call_trace.cycles
+            + self
+                .cross_calls_outcomes
+                .iter()
+                .map(ExecutionOutcome::total_cycles)
+                .sum::<u64>()
+    }
+
+    /// Returns EVM logs of the call and its successful cross-calls in execution order,
+    /// the guest appends logs of each cross-call when it returns
+    pub fn logs(&self) -> Vec<EvmLog> {
+        if self.commitment.response.is_err() {
+            return vec![];
         }
+
+        self.commitment.logs.clone()
     }
 
     pub fn prove_all(&self) -> ProvedExecutionOutcome {
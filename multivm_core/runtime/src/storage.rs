@@ -0,0 +1,95 @@
+use borsh::BorshDeserialize;
+use multivm_primitives::AccountId;
+
+fn committed_key(location: &AccountId, key: &str) -> String {
+    format!("committed_storage.{}.{}", location, key)
+}
+
+/// `@` ends the key, so history of `a.b` doesn't overlap with history of `a.b.c`
+fn history_prefix(location: &AccountId, key: &str) -> String {
+    format!("storage_history.{}.{}@", location, key)
+}
+
+fn history_key(location: &AccountId, key: &str, height: u64) -> String {
+    format!("{}{:020}", history_prefix(location, key), height)
+}
+
+pub fn get(db: &sled::Db, location: &AccountId, key: &str) -> Option<Vec<u8>> {
+    db.get(committed_key(location, key))
+        .expect("Failed to get storage from db")
+        .map(|v| v.to_vec())
+}
+
+/// Returns storage value as it was after the block at the given height
+pub fn get_at(db: &sled::Db, location: &AccountId, key: &str, height: u64) -> Option<Vec<u8>> {
+    let history = db
+        .range(history_key(location, key, 0)..=history_key(location, key, height))
+        .next_back();
+
+    match history {
+        Some(entry) => {
+            let (_, value) = entry.expect("Failed to get storage history from db");
+            Option::<Vec<u8>>::try_from_slice(&value).expect("Storage history is corrupted")
+        }
+        None => {
+            let has_history = db
+                .scan_prefix(history_prefix(location, key))
+                .next()
+                .is_some();
+            // Keys without any history were committed before it was recorded
+            if has_history {
+                None
+            } else {
+                get(db, location, key)
+            }
+        }
+    }
+}
+
+/// Keys committed before history was recorded keep their value at height 0,
+/// so `get_at` still returns it for heights before the first change
+fn record_pre_history(db: &sled::Db, location: &AccountId, key: &str) {
+    let has_history = db
+        .scan_prefix(history_prefix(location, key))
+        .next()
+        .is_some();
+    if has_history {
+        return;
+    }
+
+    if let Some(value) = get(db, location, key) {
+        db.insert(
+            history_key(location, key, 0),
+            borsh::to_vec(&Some(value)).unwrap(),
+        )
+        .expect("Failed to insert storage history to db");
+    }
+}
+
+/// Commits storage value changed at the given height
+pub fn insert(db: &sled::Db, location: &AccountId, key: &str, height: u64, value: Vec<u8>) {
+    record_pre_history(db, location, key);
+
+    db.insert(
+        history_key(location, key, height),
+        borsh::to_vec(&Some(value.clone())).unwrap(),
+    )
+    .expect("Failed to insert storage history to db");
+
+    db.insert(committed_key(location, key), value)
+        .expect("Failed to insert storage to db");
+}
+
+/// Commits storage value removed at the given height
+pub fn remove(db: &sled::Db, location: &AccountId, key: &str, height: u64) {
+    record_pre_history(db, location, key);
+
+    db.insert(
+        history_key(location, key, height),
+        borsh::to_vec(&None::<Vec<u8>>).unwrap(),
+    )
+    .expect("Failed to insert storage history to db");
+
+    db.remove(committed_key(location, key))
+        .expect("Failed to remove storage from db");
+}
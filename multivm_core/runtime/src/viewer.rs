@@ -3,12 +3,21 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use tracing::{debug, span, Level};
 
 use multivm_primitives::{
-    syscalls::{GetStorageResponse, GET_STORAGE_CALL, SET_STORAGE_CALL},
+    syscalls::{
+        GetStorageResponse, COMMIT_STORAGE_CALL, DELETE_STORAGE_CALL, EVM_TRACE_CALL,
+        GET_STORAGE_CALL, SET_STORAGE_CALL,
+    },
     AccountId, Commitment, ContractCall, ContractCallContext, ContractResponse, EnvironmentContext,
-    EvmAddress, MultiVmAccountId, SupportedTransaction,
+    EvmAddress, EvmTrace, MultiVmAccountId,
 };
 
-use crate::account::{Account, Executable};
+use crate::{
+    account::{Account, Executable},
+    bootstraper::Action,
+    code_store, storage,
+};
+
+use std::{cell::RefCell, rc::Rc};
 
 const MAX_MEMORY: u32 = 0x10000000;
 const PAGE_SIZE: u32 = 0x400;
@@ -35,12 +44,6 @@ impl SupportedView {
     }
 }
 
-#[derive(BorshDeserialize, BorshSerialize)]
-enum Action {
-    ExecuteTransaction(SupportedTransaction, EnvironmentContext),
-    View(SupportedView, EnvironmentContext),
-}
-
 pub struct Viewer {
     view: SupportedView,
     db: sled::Db,
@@ -52,8 +55,21 @@ impl Viewer {
     }
 
     pub fn account_info(account_id: &AccountId, db: sled::Db) -> Option<Account> {
-        let bytes =
-            Viewer::view_system_meta_contract("account_info".to_string(), account_id, db).unwrap();
+        Viewer::account_info_at(account_id, db, None)
+    }
+
+    pub fn account_info_at(
+        account_id: &AccountId,
+        db: sled::Db,
+        height: Option<u64>,
+    ) -> Option<Account> {
+        let bytes = Viewer::view_system_meta_contract_at(
+            "account_info".to_string(),
+            account_id,
+            db,
+            height,
+        )
+        .unwrap();
         borsh::from_slice(&bytes).unwrap()
     }
 
@@ -61,6 +77,17 @@ impl Viewer {
         method: String,
         args: &T,
         db: sled::Db,
+    ) -> ContractResponse {
+        Viewer::view_system_meta_contract_at(method, args, db, None)
+    }
+
+    /// Views system meta-contract with the state after the block at the given height,
+    /// the latest state is used if height is not set
+    pub fn view_system_meta_contract_at<T: BorshSerialize>(
+        method: String,
+        args: &T,
+        db: sled::Db,
+        height: Option<u64>,
     ) -> ContractResponse {
         let context = ContractCallContext {
             contract_id: AccountId::system_meta_contract(),
@@ -76,7 +103,10 @@ impl Viewer {
         let env = risc0_zkvm::ExecutorEnv::builder()
             .write_slice(&input_bytes)
             .session_limit(Some(u64::MAX))
-            .io_callback(GET_STORAGE_CALL, callback_on_system_get_storage(db.clone()))
+            .io_callback(
+                GET_STORAGE_CALL,
+                callback_on_system_get_storage(db.clone(), height),
+            )
             .stdout(ContractLogger::new(AccountId::system_meta_contract()))
             .build()
             .unwrap();
@@ -144,6 +174,8 @@ impl Viewer {
             .session_limit(Some(u64::MAX))
             .io_callback(GET_STORAGE_CALL, self.callback_on_get_storage())
             .io_callback(SET_STORAGE_CALL, self.callback_on_set_storage())
+            .io_callback(COMMIT_STORAGE_CALL, self.callback_on_commit_storage())
+            .io_callback(DELETE_STORAGE_CALL, self.callback_on_delete_storage())
             .stdout(ContractLogger::new(AccountId::system_meta_contract()))
             .build()
             .unwrap();
@@ -159,14 +191,48 @@ impl Viewer {
             .response
     }
 
-    fn load_contract(&self, contract_id: &MultiVmAccountId) -> Result<Vec<u8>> {
-        let db_key = format!("contracts_code.{}", contract_id.to_string());
+    /// Executes EVM view in non-proving mode and returns its trace.
+    /// Returns `None` if the view is not executed by the EVM
+    pub fn trace(self) -> Option<EvmTrace> {
+        let SupportedView::Evm(_) = self.view else {
+            return None;
+        };
+
+        let trace = Rc::new(RefCell::new(None));
+        let action = Action::TraceView(self.view.clone(), EnvironmentContext { block_height: 0 });
+        let input_bytes = borsh::to_vec(&action).unwrap();
+
+        let env = risc0_zkvm::ExecutorEnv::builder()
+            .write_slice(&input_bytes)
+            .session_limit(Some(u64::MAX))
+            .io_callback(GET_STORAGE_CALL, self.callback_on_get_storage())
+            .io_callback(SET_STORAGE_CALL, self.callback_on_set_storage())
+            .io_callback(COMMIT_STORAGE_CALL, self.callback_on_commit_storage())
+            .io_callback(DELETE_STORAGE_CALL, self.callback_on_delete_storage())
+            .io_callback(EVM_TRACE_CALL, |from_guest: risc0_zkvm::Bytes| {
+                let evm_trace = EvmTrace::try_from_slice(&from_guest).expect("Corrupted EVM trace");
+                trace.replace(Some(evm_trace));
+                Ok(Default::default())
+            })
+            .stdout(ContractLogger::new(AccountId::system_meta_contract()))
+            .build()
+            .unwrap();
+
+        let program = risc0_zkvm::Program::load_elf(
+            &meta_contracts::SYSTEM_META_CONTRACT_ELF.to_vec(),
+            MAX_MEMORY,
+        )
+        .unwrap();
+        let image = risc0_zkvm::MemoryImage::new(&program, PAGE_SIZE).unwrap();
+        let exec = risc0_zkvm::default_executor();
+
+        exec.execute(env, image).unwrap();
+
+        trace.take()
+    }
 
-        let code = self
-            .db
-            .get(db_key)
-            .expect("Failed to get storage from db")
-            .map(|v| v.to_vec())
+    fn load_contract(&self, contract_id: &MultiVmAccountId) -> Result<Vec<u8>> {
+        let code = code_store::contract_code(&self.db, &contract_id.clone().into())
             .expect("Contract not found");
 
         Ok(code)
@@ -228,10 +294,24 @@ impl Viewer {
     ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
         |_from_guest| Ok(Default::default())
     }
+
+    pub fn callback_on_delete_storage<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |_from_guest| Ok(Default::default())
+    }
+
+    /// Views don't write storage, so the storage usage isn't updated either
+    pub fn callback_on_commit_storage<'a>(
+        &'a self,
+    ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> + 'a {
+        |_from_guest| Ok(borsh::to_vec(&None::<Commitment>).unwrap().into())
+    }
 }
 
 fn callback_on_system_get_storage(
     db: sled::Db,
+    height: Option<u64>,
 ) -> impl Fn(risc0_zkvm::Bytes) -> risc0_zkvm::Result<risc0_zkvm::Bytes> {
     move |from_guest| {
         let span = span!(Level::DEBUG, "get_storage call handler");
@@ -239,16 +319,11 @@ fn callback_on_system_get_storage(
 
         let key = String::from_utf8(from_guest.into()).unwrap();
 
-        let db_key = format!(
-            "committed_storage.{}.{}",
-            AccountId::system_meta_contract(),
-            key
-        );
-
-        let storage = db
-            .get(db_key)
-            .expect("Failed to get storage from db")
-            .map(|v| v.to_vec());
+        let location = AccountId::system_meta_contract();
+        let storage = match height {
+            Some(height) => storage::get_at(&db, &location, &key, height),
+            None => storage::get(&db, &location, &key),
+        };
 
         let response = GetStorageResponse { storage };
 
@@ -5,11 +5,12 @@ use std::{collections::HashMap, sync::Mutex};
 
 use multivm_primitives::{
     syscalls::{
-        CrossContractCallRequest, GetStorageResponse, SetStorageRequest, CROSS_CONTRACT_CALL,
-        GET_STORAGE_CALL, SET_STORAGE_CALL,
+        CommitStorageRequest, CrossContractCallRequest, DeployContractRequest, GetStorageResponse,
+        SetStorageRequest, COMMIT_STORAGE_CALL, CROSS_CONTRACT_CALL, DEPLOY_CONTRACT_CALL,
+        GET_STORAGE_CALL,
     },
     AccountId, Commitment, ContractCall, ContractCallContext, ContractError, Digest as HashDigest,
-    StorageKey,
+    EvmLog, MultiVmAccountId, StorageKey,
 };
 
 pub fn setup_env(call: &ContractCallContext) {
@@ -30,6 +31,8 @@ struct Env {
     initial_storage_hashes: HashMap<StorageKey, HashDigest>,
     storage_cache: HashMap<StorageKey, (Vec<u8>, bool)>,
     cross_calls_hashes: Vec<(HashDigest, HashDigest)>,
+    /// Logs of successful cross-calls in execution order
+    logs: Vec<EvmLog>,
 }
 
 impl Env {
@@ -50,6 +53,7 @@ impl Env {
             initial_storage_hashes: Default::default(),
             storage_cache: Default::default(),
             cross_calls_hashes: Default::default(),
+            logs: Default::default(),
         }
     }
 }
@@ -98,6 +102,38 @@ impl Env {
             algorithm.finalize_reset().as_slice().try_into().unwrap()
         };
 
+        self.cross_calls_hashes.push((req_hash, output_hash));
+        if commitment.response.is_ok() {
+            self.logs.extend(commitment.logs.iter().cloned());
+        }
+
+        commitment
+    }
+
+    /// Deploys the uploaded image to a sub-account of the contract
+    pub fn deploy_contract(&mut self, req: DeployContractRequest) -> Commitment {
+        let req_hash = {
+            let req_bytes = borsh::to_vec(&req).expect("Expected to serialize");
+            let algorithm = &mut risc0_zkvm::sha::rust_crypto::Sha256::default();
+            algorithm.update(&req_bytes);
+            algorithm.finalize_reset().as_slice().try_into().unwrap()
+        };
+
+        let response = risc0_zkvm::guest::env::send_recv_slice(
+            DEPLOY_CONTRACT_CALL,
+            borsh::to_vec(&req).unwrap().as_slice(),
+        )
+        .to_vec();
+
+        let commitment = Commitment::try_from_bytes(response).expect("Commitment is corrupted");
+
+        let response_bytes = borsh::to_vec(&commitment.response).expect("Expected to serialize");
+        let output_hash = {
+            let algorithm = &mut risc0_zkvm::sha::rust_crypto::Sha256::default();
+            algorithm.update(&response_bytes);
+            algorithm.finalize_reset().as_slice().try_into().unwrap()
+        };
+
         self.cross_calls_hashes.push((req_hash, output_hash));
 
         commitment
@@ -147,28 +183,62 @@ impl Env {
             .insert(key.clone(), (storage_bytes.clone(), true));
     }
 
-    fn send_storage_update(key: String, storage: Vec<u8>) -> HashDigest {
+    fn storage_update(key: String, storage: Vec<u8>) -> SetStorageRequest {
         let hash = {
             let algorithm = &mut Sha256::default();
             algorithm.update(&storage);
             algorithm.finalize_reset().as_slice().try_into().unwrap()
         };
 
-        let request = SetStorageRequest {
-            key: key.clone(),
-            hash,
-            storage,
-        };
+        SetStorageRequest { key, hash, storage }
+    }
 
-        let to_host = borsh::to_vec(&request).expect("Expected to serialize");
+    /// Writes the changed storage at once, returns commitment of the storage usage update
+    /// made by the host, if any. The update fails if the balance doesn't cover the storage
+    fn send_storage_commit(&mut self, request: CommitStorageRequest) -> Option<Commitment> {
+        let req_bytes = borsh::to_vec(&request).expect("Expected to serialize");
+        let req_hash = {
+            let algorithm = &mut risc0_zkvm::sha::rust_crypto::Sha256::default();
+            algorithm.update(&req_bytes);
+            algorithm.finalize_reset().as_slice().try_into().unwrap()
+        };
 
-        let _: Vec<u8> =
-            risc0_zkvm::guest::env::send_recv_slice(SET_STORAGE_CALL, &to_host).to_vec();
+        let response =
+            risc0_zkvm::guest::env::send_recv_slice(COMMIT_STORAGE_CALL, &req_bytes).to_vec();
+        let commitment: Option<Commitment> =
+            borsh::from_slice(&response).expect("Storage commit response is corrupted");
+
+        if let Some(commitment) = &commitment {
+            let response_bytes =
+                borsh::to_vec(&commitment.response).expect("Expected to serialize");
+            let output_hash = {
+                let algorithm = &mut risc0_zkvm::sha::rust_crypto::Sha256::default();
+                algorithm.update(&response_bytes);
+                algorithm.finalize_reset().as_slice().try_into().unwrap()
+            };
+
+            self.cross_calls_hashes.push((req_hash, output_hash));
+        }
 
-        hash
+        commitment
     }
 
-    pub fn commit<T: borsh::BorshSerialize>(self, output: T) {
+    pub fn commit<T: borsh::BorshSerialize>(mut self, output: T) {
+        let updates: Vec<_> = std::mem::take(&mut self.storage_cache)
+            .into_iter()
+            .filter(|(_, (_, was_changed))| *was_changed)
+            .map(|(key, (storage, _))| Env::storage_update(key, storage))
+            .collect();
+
+        let mut response = Ok(borsh::to_vec(&output).expect("Expected to serialize"));
+        if !updates.is_empty() {
+            if let Some(commitment) = self.send_storage_commit(CommitStorageRequest { updates }) {
+                if let Err(error) = commitment.response {
+                    response = Err(error);
+                }
+            }
+        }
+
         let Env {
             signer_id: _,
             caller_id: _,
@@ -176,26 +246,20 @@ impl Env {
             gas: _,
             call_hash,
             initial_storage_hashes: _,
-            storage_cache,
+            storage_cache: _,
             cross_calls_hashes,
+            logs,
         } = self;
 
-        let response = borsh::to_vec(&output).expect("Expected to serialize");
-
-        storage_cache
-            .into_iter()
-            .for_each(|(key, (storage, was_changed))| {
-                if was_changed {
-                    Env::send_storage_update(key.clone(), storage.clone());
-                }
-            });
-
+        // Failed call has no effects, logs included
+        let logs = if response.is_ok() { logs } else { vec![] };
         let commitment = Commitment {
-            response: Ok(response),
+            response,
             call_hash: call_hash,
             cross_calls_hashes: cross_calls_hashes,
             previous_account_root: Default::default(),
             new_account_root: Default::default(),
+            logs,
         };
 
         risc0_zkvm::guest::env::commit_slice(
@@ -213,6 +277,7 @@ impl Env {
             initial_storage_hashes: _, // TODO: fix  storage
             storage_cache: _,
             cross_calls_hashes,
+            logs: _,
         } = self;
 
         let commitment = Commitment {
@@ -221,6 +286,7 @@ impl Env {
             cross_calls_hashes: cross_calls_hashes,
             previous_account_root: Default::default(),
             new_account_root: Default::default(),
+            logs: Default::default(),
         };
 
         risc0_zkvm::guest::env::commit_slice(
@@ -278,6 +344,16 @@ pub fn cross_contract_call_raw(
         .cross_contract_call(req.into())
 }
 
+/// Creates sub-account of the current contract, e.g. `pool1.amm.multivm` for `amm.multivm`,
+/// and deploys the image to it. The image has to be uploaded to the code store
+pub fn deploy_contract(account_id: MultiVmAccountId, image_id: [u32; 8]) -> Commitment {
+    let req = DeployContractRequest {
+        account_id: account_id.into(),
+        image_id,
+    };
+    ENV.lock().unwrap().as_mut().unwrap().deploy_contract(req)
+}
+
 /// Returns the storage value for the given key, return None if storage is not exist
 pub fn get_storage<T: BorshDeserialize>(key: StorageKey) -> Option<T> {
     ENV.lock().unwrap().as_mut().unwrap().get_storage(key)